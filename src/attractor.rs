@@ -0,0 +1,282 @@
+use clap::ValueEnum;
+
+use crate::cli::View;
+use crate::density::{self, Histogram};
+use crate::palette::Palette;
+use crate::random::Rng;
+use crate::{Complex, Draw, Image, Settings};
+
+/// The maps of the attractors, with the names of their parameters.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Map {
+    /// `x = sin(a y) + c cos(a x)`, `y = sin(b x) + d cos(b y)`
+    Clifford,
+    /// `x = sin(a y) - cos(b x)`, `y = sin(c x) - cos(d y)`
+    DeJong,
+    /// `x = 1 - a x^2 + y`, `y = b x`
+    Henon,
+    /// `x = 1 + u (x cos t - y sin t)`, `y = u (x sin t + y cos t)` with
+    /// `t = 0.4 - 6 / (1 + x^2 + y^2)`
+    Ikeda,
+    /// `x = y + a y (1 - b y^2) + f(x)`, `y = f(x') - x` with
+    /// `f(x) = mu x + 2 (1 - mu) x^2 / (1 + x^2)`
+    GumowskiMira,
+    /// The Lorenz system with parameters `sigma`, `rho` and `beta`, integrated
+    /// in time steps of `dt` and projected onto its `x` and `z` axes.
+    Lorenz,
+}
+
+impl Map {
+    /// The parameters and their default values.
+    pub fn parameters(self) -> &'static [(&'static str, f64)] {
+        match self {
+            Map::Clifford => &[("a", -1.4), ("b", 1.6), ("c", 1.0), ("d", 0.7)],
+            Map::DeJong => &[("a", 1.641), ("b", 1.902), ("c", 0.316), ("d", 1.525)],
+            Map::Henon => &[("a", 1.4), ("b", 0.3)],
+            Map::Ikeda => &[("u", 0.9)],
+            Map::GumowskiMira => &[("a", 0.008), ("b", 0.05), ("mu", -0.496)],
+            Map::Lorenz => &[
+                ("sigma", 10.0),
+                ("rho", 28.0),
+                ("beta", 8.0 / 3.0),
+                ("dt", 0.005),
+            ],
+        }
+    }
+}
+
+/// One of the [`Map`]s with its parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attractor {
+    pub map: Map,
+    /// Values of the parameters, in the order of [`Map::parameters`].
+    pub values: Vec<f64>,
+}
+
+impl Attractor {
+    /// The attractor with the defaults of `map`, changed by `params`.
+    pub fn new(map: Map, params: &[(String, Complex)]) -> Result<Self, String> {
+        let parameters = map.parameters();
+        let mut values: Vec<f64> = parameters.iter().map(|&(_, value)| value).collect();
+        for (name, value) in params {
+            let Some(i) = parameters.iter().position(|(known, _)| known == name) else {
+                let known: Vec<&str> = parameters.iter().map(|&(name, _)| name).collect();
+                return Err(format!(
+                    "unknown parameter '{name}'; this attractor has {}",
+                    known.join(", ")
+                ));
+            };
+            if value.im != 0.0 {
+                return Err(format!("the parameter '{name}' must be real"));
+            }
+            values[i] = value.re;
+        }
+        Ok(Self { map, values })
+    }
+
+    /// The next point. Only the Lorenz system uses the third coordinate.
+    pub fn step(&self, [x, y, z]: [f64; 3]) -> [f64; 3] {
+        let v = &self.values;
+        match self.map {
+            Map::Clifford => [
+                (v[0] * y).sin() + v[2] * (v[0] * x).cos(),
+                (v[1] * x).sin() + v[3] * (v[1] * y).cos(),
+                0.0,
+            ],
+            Map::DeJong => [
+                (v[0] * y).sin() - (v[1] * x).cos(),
+                (v[2] * x).sin() - (v[3] * y).cos(),
+                0.0,
+            ],
+            Map::Henon => [1.0 - v[0] * x * x + y, v[1] * x, 0.0],
+            Map::Ikeda => {
+                let t = 0.4 - 6.0 / (1.0 + x * x + y * y);
+                let (sin, cos) = t.sin_cos();
+                [
+                    1.0 + v[0] * (x * cos - y * sin),
+                    v[0] * (x * sin + y * cos),
+                    0.0,
+                ]
+            }
+            Map::GumowskiMira => {
+                let f = |x: f64| v[2] * x + 2.0 * (1.0 - v[2]) * x * x / (1.0 + x * x);
+                let next = y + v[0] * y * (1.0 - v[1] * y * y) + f(x);
+                [next, f(next) - x, 0.0]
+            }
+            Map::Lorenz => {
+                let (sigma, rho, beta, dt) = (v[0], v[1], v[2], v[3]);
+                let velocity =
+                    |[x, y, z]: [f64; 3]| [sigma * (y - x), x * (rho - z) - y, x * y - beta * z];
+                // A fourth-order Runge–Kutta step.
+                let along = |p: [f64; 3], k: [f64; 3], h: f64| {
+                    [p[0] + h * k[0], p[1] + h * k[1], p[2] + h * k[2]]
+                };
+                let p = [x, y, z];
+                let k1 = velocity(p);
+                let k2 = velocity(along(p, k1, dt / 2.0));
+                let k3 = velocity(along(p, k2, dt / 2.0));
+                let k4 = velocity(along(p, k3, dt));
+                let k: [f64; 3] =
+                    std::array::from_fn(|i| (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]) / 6.0);
+                along(p, k, dt)
+            }
+        }
+    }
+
+    /// Where a point is drawn in the plane.
+    pub fn project(&self, [x, y, z]: [f64; 3]) -> Complex {
+        match self.map {
+            Map::Lorenz => Complex::new(x, z),
+            _ => Complex::new(x, y),
+        }
+    }
+}
+
+/// Points of an attractor computed from one random start. Each chunk first
+/// lets its orbit settle onto the attractor.
+const CHUNK: usize = 1 << 16;
+
+/// Steps taken before a chunk starts plotting.
+const WARMUP: usize = 1000;
+
+/// Points of the orbit that find the region an attractor fills.
+const PILOT: usize = CHUNK;
+
+/// Draws an [`Attractor`] as the density of a long orbit.
+pub struct AttractorDensity {
+    pub attractor: Attractor,
+    pub points: usize,
+    pub seed: u64,
+    pub palette: Palette,
+    /// The region to draw, or `None` to fit it to the attractor.
+    pub view: Option<View>,
+}
+
+impl AttractorDensity {
+    /// A point on the attractor, reached from the random start of `chunk`.
+    fn start(&self, chunk: usize) -> [f64; 3] {
+        let mut rng = Rng::stream(self.seed, chunk as u64);
+        let mut p = [
+            rng.range(-0.1, 0.1),
+            rng.range(-0.1, 0.1),
+            rng.range(0.9, 1.1),
+        ];
+        for _ in 0..WARMUP {
+            p = self.attractor.step(p);
+        }
+        p
+    }
+
+    /// The region the first points of the orbit fill, with a margin, in the
+    /// proportions of a `width` by `height` image.
+    pub fn fit(&self, width: usize, height: usize) -> View {
+        let orbit = std::iter::successors(Some(self.start(0)), |&p| Some(self.attractor.step(p)))
+            .take(PILOT)
+            .take_while(|p| p.iter().all(|v| v.is_finite()));
+        density::fit(orbit.map(|p| self.attractor.project(p)), width, height)
+    }
+
+    pub fn accumulate(&self, settings: &Settings) -> Histogram {
+        let (width, height) = (settings.width, settings.height);
+        let view = self.view.unwrap_or_else(|| self.fit(width, height));
+        let chunks = self.points.div_ceil(CHUNK);
+        let empty = || Histogram::upright(width, height, view);
+        density::accumulate(settings.threads, chunks, empty, |chunk, histogram| {
+            let mut p = self.start(chunk);
+            for _ in chunk * CHUNK..self.points.min((chunk + 1) * CHUNK) {
+                if !p.iter().all(|v| v.is_finite()) {
+                    break;
+                }
+                histogram.plot(self.attractor.project(p));
+                p = self.attractor.step(p);
+            }
+        })
+    }
+}
+
+impl Draw for AttractorDensity {
+    fn draw(&self, settings: &Settings) -> Image {
+        self.accumulate(settings).log_image(&self.palette)
+    }
+}
+
+#[test]
+fn test_attractor() {
+    let param = |name: &str, value| (name.to_string(), Complex::new(value, 0.0));
+    let henon = Attractor::new(Map::Henon, &[param("b", 0.2)]).unwrap();
+    assert!(henon.values == [1.4, 0.2]);
+    assert!(henon.step([0.5, 0.1, 0.0]) == [1.0 - 1.4 * 0.25 + 0.1, 0.1, 0.0]);
+    assert!(Attractor::new(Map::Henon, &[param("c", 1.0)]).is_err());
+    assert!(
+        Attractor::new(Map::Henon, &[("a".to_string(), Complex::I)]).is_err(),
+        "A complex parameter was accepted."
+    );
+
+    // The Lorenz system keeps circling its two wings.
+    let lorenz = Attractor::new(Map::Lorenz, &[]).unwrap();
+    let mut p = [1.0, 1.0, 1.0];
+    let (mut left, mut right) = (0, 0);
+    for _ in 0..20000 {
+        p = lorenz.step(p);
+        assert!(p[2] > -1.0 && p[2] < 60.0, "The orbit left the attractor.");
+        if p[0] < 0.0 {
+            left += 1;
+        } else {
+            right += 1;
+        }
+    }
+    assert!(left > 1000 && right > 1000);
+    assert!(lorenz.project(p) == Complex::new(p[0], p[2]));
+}
+
+#[test]
+fn test_attractor_density() {
+    let mut settings = density::test_settings(1);
+    let density = AttractorDensity {
+        attractor: Attractor::new(Map::Clifford, &[]).unwrap(),
+        points: 2 * CHUNK + 100,
+        seed: 3,
+        palette: Palette::builtin("gray").unwrap(),
+        view: Some(settings.view),
+    };
+    let one = density.accumulate(&settings);
+    settings.threads = 3;
+    assert!(one.counts == density.accumulate(&settings).counts);
+    let total: u64 = one.counts.iter().map(|&n| n as u64).sum();
+    assert!(
+        total == density.points as u64,
+        "The Clifford attractor lies inside [-2, 2]."
+    );
+
+    // The Lorenz attractor is fitted around its wings, with z upward.
+    let lorenz = AttractorDensity {
+        attractor: Attractor::new(Map::Lorenz, &[]).unwrap(),
+        view: None,
+        ..density
+    };
+    let view = lorenz.fit(32, 32);
+    assert!(view.from_x < -15.0 && view.to_x > 15.0);
+    assert!(view.from_y > 0.0 && view.to_y > 40.0 && view.to_y < 60.0);
+    let counts = lorenz.accumulate(&settings).counts;
+    let total: u64 = counts.iter().map(|&n| n as u64).sum();
+    assert!(
+        total > 99 * density.points as u64 / 100,
+        "The fit cut off the attractor."
+    );
+
+    // The highest point of the orbit is drawn near the top.
+    let mut p = lorenz.start(0);
+    let mut top = lorenz.attractor.project(p);
+    for _ in 0..CHUNK {
+        p = lorenz.attractor.step(p);
+        if lorenz.attractor.project(p).im > top.im {
+            top = lorenz.attractor.project(p);
+        }
+    }
+    let x = ((top.re - view.from_x) / (view.to_x - view.from_x) * 32.0) as usize;
+    let y = ((view.to_y - top.im) / (view.to_y - view.from_y) * 32.0) as usize;
+    assert!(
+        y < 3 && counts[y * 32 + x] > 0,
+        "The attractor is upside down."
+    );
+}
@@ -0,0 +1,154 @@
+use crate::fractal::{Escape, Fractal, Orbit};
+use crate::Complex;
+
+/// A statistic gathered over an orbit while it is iterated, for colorings
+/// that look at the whole orbit rather than where it ended.
+pub trait Accumulator {
+    /// Takes in the state of the orbit after a step.
+    fn add(&mut self, orbit: &Orbit);
+
+    /// The statistic as a coloring value, given the
+    /// [`Escape::fraction`] of the orbit.
+    fn value(&self, fraction: f64) -> f64;
+}
+
+/// Iterates the orbit of `pixel` like [`Fractal::escape`], feeding every
+/// step to `accumulator`.
+pub fn accumulate(
+    fractal: &dyn Fractal,
+    pixel: Complex,
+    bailout_sqr: f64,
+    max_iterations: usize,
+    accumulator: &mut dyn Accumulator,
+) -> Escape {
+    fractal.escape_with(pixel, bailout_sqr, max_iterations, &mut |orbit| {
+        accumulator.add(orbit)
+    })
+}
+
+/// The mean of a sequence of terms, and the mean without its last term.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Mean {
+    sum: f64,
+    last: f64,
+    count: usize,
+}
+
+impl Mean {
+    pub fn add(&mut self, term: f64) {
+        self.sum += term;
+        self.last = term;
+        self.count += 1;
+    }
+
+    /// Blends the mean without the last term into the full mean by
+    /// `fraction`, which keeps the value continuous where the iteration
+    /// count jumps.
+    pub fn interpolate(&self, fraction: f64) -> f64 {
+        let mean = |sum: f64, count: usize| if count > 0 { sum / count as f64 } else { 0.0 };
+        let all = mean(self.sum, self.count);
+        let previous = match self.count {
+            0 | 1 => all,
+            n => mean(self.sum - self.last, n - 1),
+        };
+        previous + fraction * (all - previous)
+    }
+}
+
+/// The triangle inequality average: where every `|z^d + c|` falls between
+/// its bounds `||z|^d - |c||` and `|z|^d + |c|`, averaged over the orbit.
+pub struct TriangleInequality {
+    pub degree: f64,
+    pub mean: Mean,
+}
+
+impl TriangleInequality {
+    pub fn new(degree: f64) -> Self {
+        Self {
+            degree,
+            mean: Mean::default(),
+        }
+    }
+}
+
+impl Accumulator for TriangleInequality {
+    fn add(&mut self, orbit: &Orbit) {
+        let power = orbit.previous.abs().powf(self.degree);
+        let c = orbit.c.abs();
+        let low = (power - c).abs();
+        let high = power + c;
+        // The first step from zero has no width to fall between.
+        if high > low {
+            self.mean.add((orbit.z.abs() - low) / (high - low));
+        }
+    }
+
+    fn value(&self, fraction: f64) -> f64 {
+        self.mean.interpolate(fraction)
+    }
+}
+
+/// The stripe average: `(1 + sin(density arg z)) / 2` averaged over the
+/// orbit, which draws stripes that follow the arms of the fractal.
+pub struct Stripes {
+    pub density: f64,
+    pub mean: Mean,
+}
+
+impl Stripes {
+    pub fn new(density: f64) -> Self {
+        Self {
+            density,
+            mean: Mean::default(),
+        }
+    }
+}
+
+impl Accumulator for Stripes {
+    fn add(&mut self, orbit: &Orbit) {
+        self.mean
+            .add(0.5 + 0.5 * (self.density * orbit.z.arg()).sin());
+    }
+
+    fn value(&self, fraction: f64) -> f64 {
+        self.mean.interpolate(fraction)
+    }
+}
+
+#[test]
+fn test_mean() {
+    let mut mean = Mean::default();
+    assert!(mean.interpolate(0.5) == 0.0);
+    mean.add(1.0);
+    assert!(mean.interpolate(0.0) == 1.0);
+    mean.add(2.0);
+    mean.add(6.0);
+    assert!(mean.interpolate(1.0) == 3.0);
+    assert!(mean.interpolate(0.0) == 1.5);
+    assert!(mean.interpolate(0.5) == 2.25);
+}
+
+#[test]
+fn test_accumulators() {
+    use crate::fractal::{Julia, Mandelbrot};
+
+    // On the real line, z^2 + c with positive z and c reaches the upper
+    // bound every time, except for the first step from zero.
+    let mut tia = TriangleInequality::new(2.0);
+    let escape = accumulate(&Mandelbrot, Complex::new(0.3, 0.0), 4.0, 50, &mut tia);
+    assert!(escape.iterations > 1 && tia.mean.count == escape.iterations - 1);
+    assert!((tia.value(0.3) - 1.0).abs() < 1e-12);
+    let mut tia = TriangleInequality::new(2.0);
+    accumulate(&Mandelbrot, Complex::new(-0.1, 0.0), 4.0, 50, &mut tia);
+    assert!(tia.value(1.0) < 1.0);
+
+    // Every z of a Julia orbit with c = 0 stays on the real line.
+    let mut stripes = Stripes::new(5.0);
+    let julia = Julia { c: Complex::ZERO };
+    let escape = accumulate(&julia, Complex::new(1.5, 0.0), 1e6, 50, &mut stripes);
+    assert!(stripes.mean.count == escape.iterations);
+    assert!(
+        (stripes.value(0.7) - 0.5).abs() < 1e-12,
+        "The real line has no stripes."
+    );
+}
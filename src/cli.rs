@@ -0,0 +1,964 @@
+use std::thread;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::attractor::{Attractor, AttractorDensity, Map};
+use crate::density::Buddhabrot;
+use crate::flame::{Flame, FlameRender, MAX_SUPERSAMPLE};
+use crate::formula::{Formula, FormulaSource};
+use crate::fractal::{
+    Biomorph, Exponent, Fractal, Julia, Mandelbrot, Multibrot, Recurrence, RecurrenceSet,
+    Transcendental, TranscendentalSet, Variant, Variation,
+};
+use crate::ifs::{ChaosGame, Ifs};
+use crate::lsystem::{parse_lsystem, parse_rule, LSystem, Turtle};
+use crate::lyapunov::{parse_sequence, Lyapunov, Sequence};
+use crate::newton::{Method, Newton, NewtonShader, Nova, NovaShader, Polynomial};
+use crate::palette::{Interpolation, Mode, Palette};
+use crate::shader::{Coloring, EscapeTime, Shader};
+use crate::trap::{self, Picture, Shape, Trap};
+use crate::{Complex, Renderer};
+
+#[derive(Parser)]
+#[command(version, about = "Renders fractals to PNG images.")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Render a fractal to a PNG file.
+    Render(Box<RenderArgs>),
+    /// List the available fractals and palettes.
+    List,
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+    /// Point in the middle of the image, as `re,im`.
+    #[arg(long, value_parser = parse_complex, allow_hyphen_values = true, default_value = "0,0")]
+    pub center: Complex,
+
+    /// Magnification; 1 shows a region 4 units tall.
+    #[arg(long, value_parser = parse_zoom, default_value_t = 1.0)]
+    pub zoom: f64,
+
+    /// Explicit region as `from_x,to_x,from_y,to_y`, instead of --center and --zoom.
+    #[arg(long, value_parser = parse_bounds, allow_hyphen_values = true, conflicts_with_all = ["center", "zoom"])]
+    pub bounds: Option<View>,
+
+    /// Image size in pixels, as `WIDTHxHEIGHT` or a single number for a square,
+    /// with at most 16384x16384 pixels.
+    #[arg(long, value_parser = parse_size, default_value = "1024")]
+    pub size: (usize, usize),
+
+    /// Maximum number of iterations per pixel, or `auto` to raise it with the zoom.
+    #[arg(long, value_parser = parse_iterations, default_value = "auto")]
+    pub iterations: Iterations,
+
+    /// Output file; `.png` is appended if missing.
+    #[arg(short, long, default_value = "output")]
+    pub output: String,
+
+    #[arg(long, value_enum, default_value_t = FractalKind::Julia)]
+    pub fractal: FractalKind,
+
+    /// Render the Julia flavor of a Mandelbrot-like fractal, using --c.
+    #[arg(long)]
+    pub julia: bool,
+
+    /// Power `d` of the `multibrot` fractal `z^d + c`; an integer, a real
+    /// number or a complex number such as `2.5+0.1i`.
+    #[arg(long, value_parser = parse_complex, allow_hyphen_values = true, default_value = "3")]
+    pub power: Complex,
+
+    /// Constant `c` of the Julia set, as `re,im`.
+    #[arg(long = "c", value_parser = parse_complex, allow_hyphen_values = true, default_value = "-0.4,0.5868")]
+    pub julia_c: Complex,
+
+    /// Weight `p` of the previous `z` in the `phoenix` fractal.
+    #[arg(long = "p", value_parser = parse_complex, allow_hyphen_values = true, default_value = "-0.5")]
+    pub phoenix_p: Complex,
+
+    /// The map of an `attractor`; its parameters are set with --param. The
+    /// attractor is fitted to the image unless --bounds is given.
+    #[arg(long, value_enum, default_value_t = Map::Clifford)]
+    pub attractor: Map,
+
+    /// The system of an `ifs` fractal: a built-in name, a Fractint `.ifs`
+    /// file with an optional `:entry`, or maps `a b c d e f p` separated by
+    /// `;`, where `x' = a x + b y + e`, `y' = c x + d y + f` is chosen with
+    /// probability `p`. The attractor is fitted to the image unless --bounds
+    /// is given.
+    #[arg(long, value_parser = Ifs::parse, default_value = "fern", allow_hyphen_values = true)]
+    pub ifs: Ifs,
+
+    /// The flame of a `flame` fractal: a built-in name or an Apophysis or
+    /// flam3 `.flame` file with an optional `:name`. The palette and view
+    /// saved in the file take the place of --palette and --center.
+    #[arg(long, value_parser = Flame::parse, default_value = "julia", help_heading = "Flame")]
+    pub flame: Flame,
+
+    /// Gamma of a flame's brightness; defaults to the file's, or 4.
+    #[arg(long, value_parser = parse_positive, help_heading = "Flame")]
+    pub gamma: Option<f64>,
+
+    /// How much gamma leaves a flame's colors saturated, from 0 to 1;
+    /// defaults to the file's, or 1.
+    #[arg(long, value_parser = parse_unit, help_heading = "Flame")]
+    pub vibrancy: Option<f64>,
+
+    /// Samples per pixel along each axis of a flame, at most 8; defaults to
+    /// the file's, or 2.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=MAX_SUPERSAMPLE as u64), help_heading = "Flame")]
+    pub supersample: Option<u64>,
+
+    /// The L-system of an `lsystem` fractal, changed by the options below.
+    #[arg(long, value_parser = parse_lsystem, default_value = "koch", help_heading = "L-system")]
+    pub lsystem: LSystem,
+
+    /// Start of the L-system in place of the preset's, e.g. `F--F--F`. The
+    /// turtle draws a step for `F` and `G`, moves for `f` and `g`, turns with
+    /// `+`, `-` and `|`, and branches with `[` and `]`.
+    #[arg(long, allow_hyphen_values = true, help_heading = "L-system")]
+    pub axiom: Option<String>,
+
+    /// A rule `SYMBOL=REPLACEMENT`, replacing the preset's rule for the
+    /// symbol; may be repeated.
+    #[arg(long = "rule", value_parser = parse_rule, allow_hyphen_values = true, help_heading = "L-system")]
+    pub rules: Vec<(char, String)>,
+
+    /// Turning angle of the L-system in degrees.
+    #[arg(long, value_parser = parse_number, allow_hyphen_values = true, help_heading = "L-system")]
+    pub angle: Option<f64>,
+
+    /// Number of times the L-system's rules are applied.
+    #[arg(long, help_heading = "L-system")]
+    pub depth: Option<usize>,
+
+    /// Width of the L-system's lines in pixels. An `.svg` of the lines is
+    /// saved next to the image.
+    #[arg(long, value_parser = parse_positive, default_value_t = 1.0, help_heading = "L-system")]
+    pub line_width: f64,
+
+    /// Order in which a `lyapunov` fractal applies the rates `a` and `b`,
+    /// e.g. `AABAB`.
+    #[arg(long, value_parser = parse_sequence, default_value = "AB")]
+    pub sequence: Sequence,
+
+    /// Step of a `formula` fractal, e.g. `z^3 + c` or `sin(z) * c`. Formulas
+    /// may use `z`, `c`, `pixel`, --param names, `i`, `pi`, `e`,
+    /// `+ - * / ^`, `|z|` and sin, cos, tan, sinh, cosh, tanh, exp, ln,
+    /// sqrt, abs, norm, arg, re, im and conj; --formula-bailout may also use
+    /// the radius `bailout`.
+    #[arg(long, default_value = "z^2 + c", help_heading = "Formula")]
+    pub formula: String,
+
+    /// Starting `z` of a `formula` fractal.
+    #[arg(
+        long,
+        default_value = "0",
+        allow_hyphen_values = true,
+        help_heading = "Formula"
+    )]
+    pub formula_init: String,
+
+    /// Constant `c` of a `formula` fractal; may use `pixel` but not `z`.
+    #[arg(
+        long,
+        default_value = "pixel",
+        allow_hyphen_values = true,
+        help_heading = "Formula"
+    )]
+    pub formula_c: String,
+
+    /// Condition under which a `formula` orbit has escaped, e.g. `re(z) > 50`.
+    #[arg(long, default_value = "|z| > bailout", help_heading = "Formula")]
+    pub formula_bailout: String,
+
+    /// A named constant for formulas, or a parameter of an attractor, as
+    /// `NAME=VALUE`; may be repeated.
+    #[arg(long = "param", value_parser = parse_param, allow_hyphen_values = true, help_heading = "Formula")]
+    pub params: Vec<(String, Complex)>,
+
+    /// Roots of the `newton` polynomial, separated by `;`, e.g. `1;i;-1;-i`.
+    #[arg(long, value_parser = parse_complex, value_delimiter = ';', allow_hyphen_values = true, conflicts_with = "coefficients", help_heading = "Newton")]
+    pub roots: Vec<Complex>,
+
+    /// Coefficients of the `newton` polynomial, highest degree first and
+    /// separated by `;`; defaults to `1;0;0;-1`, which is `z^3 - 1`.
+    #[arg(long, value_parser = parse_complex, value_delimiter = ';', allow_hyphen_values = true, help_heading = "Newton")]
+    pub coefficients: Vec<Complex>,
+
+    /// Step length below which a root-finding orbit counts as converged.
+    #[arg(long, value_parser = parse_positive, default_value_t = 1e-6, help_heading = "Newton")]
+    pub tolerance: f64,
+
+    /// Factor `R` applied to every root-finding step, as in `z - R*p/p'`.
+    #[arg(long, value_parser = parse_complex, allow_hyphen_values = true, default_value = "1", help_heading = "Newton")]
+    pub relaxation: Complex,
+
+    /// A built-in palette name, or stops such as `#000000,#ff8000@0.3,#ffffff`.
+    #[arg(long, value_parser = Palette::parse, default_value = "fire", help_heading = "Coloring")]
+    pub palette: Palette,
+
+    /// Palette for the chaotic regions of a `lyapunov` fractal, which uses
+    /// --palette for the stable ones.
+    #[arg(long, value_parser = Palette::parse, default_value = "ocean", help_heading = "Coloring")]
+    pub chaotic_palette: Palette,
+
+    /// Color space for blending stops, overriding the palette's own.
+    #[arg(long, value_enum, help_heading = "Coloring")]
+    pub interpolation: Option<Interpolation>,
+
+    /// Whether the palette wraps around or stops at its ends, overriding the palette's own.
+    #[arg(long, value_enum, help_heading = "Coloring")]
+    pub palette_mode: Option<Mode>,
+
+    /// Shift along the palette.
+    #[arg(long, value_parser = parse_number, allow_hyphen_values = true, default_value_t = 0.0, help_heading = "Coloring")]
+    pub palette_offset: f64,
+
+    /// Factor applied to the coloring value before the offset.
+    #[arg(long, value_parser = parse_number, allow_hyphen_values = true, default_value_t = 1.0, help_heading = "Coloring")]
+    pub palette_scale: f64,
+
+    /// How many times the palette repeats over the coloring range.
+    #[arg(long, value_parser = parse_positive, default_value_t = 1.0, help_heading = "Coloring")]
+    pub palette_repeat: f64,
+
+    /// Color by the continuous (normalized) iteration count instead of the
+    /// whole number of iterations, which removes banding.
+    #[arg(long, help_heading = "Coloring")]
+    pub smooth: bool,
+
+    /// What escape-time fractals are colored by.
+    #[arg(long, value_enum, default_value_t = ColoringKind::Iterations, help_heading = "Coloring")]
+    pub coloring: ColoringKind,
+
+    /// How close to the axes an orbit must come to be part of a stalk.
+    #[arg(long, value_parser = parse_positive, default_value_t = 0.05, help_heading = "Coloring")]
+    pub stalk_width: f64,
+
+    /// Number of stripes per turn around the origin for `--coloring stripes`.
+    #[arg(long, value_parser = parse_positive, default_value_t = 5.0, help_heading = "Coloring")]
+    pub stripe_density: f64,
+
+    /// Shape of the orbit trap used by `--coloring trap`.
+    #[arg(long, value_enum, default_value_t = TrapKind::Point, help_heading = "Orbit trap")]
+    pub trap: TrapKind,
+
+    /// Center of the trap.
+    #[arg(long, value_parser = parse_complex, allow_hyphen_values = true, default_value = "0,0", help_heading = "Orbit trap")]
+    pub trap_center: Complex,
+
+    /// Angle of a line or cross trap, in degrees.
+    #[arg(long, value_parser = parse_number, allow_hyphen_values = true, default_value_t = 0.0, help_heading = "Orbit trap")]
+    pub trap_angle: f64,
+
+    /// Radius of a circle trap.
+    #[arg(long, value_parser = parse_positive, default_value_t = 1.0, help_heading = "Orbit trap")]
+    pub trap_radius: f64,
+
+    /// How close an orbit must come to fall into the trap, and the scale of
+    /// its distances; half the width of an image trap.
+    #[arg(long, value_parser = parse_positive, default_value_t = 0.1, help_heading = "Orbit trap")]
+    pub trap_size: f64,
+
+    /// What trapped orbits are colored by.
+    #[arg(long, value_enum, default_value_t = trap::Mode::Minimum, help_heading = "Orbit trap")]
+    pub trap_mode: trap::Mode,
+
+    /// PNG file of an image trap; transparent pixels let orbits through.
+    #[arg(long, value_parser = Picture::load, help_heading = "Orbit trap")]
+    pub trap_image: Option<Picture>,
+
+    /// Escape radius, or the bound of the fractal's own escape test; defaults
+    /// to 2, or 256 with --smooth or an averaging coloring, and to 50 for
+    /// transcendental maps.
+    #[arg(long, value_parser = parse_positive)]
+    pub bailout: Option<f64>,
+
+    /// Draw the density of orbits instead of coloring every point by its own
+    /// orbit.
+    #[arg(long, value_enum, help_heading = "Density")]
+    pub density: Option<Density>,
+
+    /// Number of random points whose orbits are drawn, or of points of an
+    /// attractor, system or flame; defaults to ten per pixel, or a hundred for
+    /// attractors, systems and flames.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), help_heading = "Density")]
+    pub samples: Option<u64>,
+
+    /// Seed for the random points; the same seed gives the same image.
+    #[arg(long, default_value_t = 0, help_heading = "Density")]
+    pub seed: u64,
+
+    /// Iteration limits of the red, green and blue channels of a Nebulabrot.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "5000,500,50",
+        help_heading = "Density"
+    )]
+    pub limits: Vec<usize>,
+
+    /// Number of worker threads; defaults to one per core.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub threads: Option<u64>,
+}
+
+impl RenderArgs {
+    /// The region of the complex plane to render, keeping square pixels when
+    /// it is derived from --center and --zoom.
+    pub fn view(&self) -> View {
+        if let Some(view) = self.bounds {
+            return view;
+        }
+
+        let (width, height) = self.size;
+        let half_height = 2.0 / self.zoom;
+        let half_width = half_height * width as f64 / height as f64;
+        View {
+            from_x: self.center.re - half_width,
+            to_x: self.center.re + half_width,
+            from_y: self.center.im - half_height,
+            to_y: self.center.im + half_height,
+        }
+    }
+
+    /// The iteration limit, resolving `auto` against the magnification of
+    /// the view.
+    pub fn max_iterations(&self) -> usize {
+        match self.iterations {
+            Iterations::Fixed(n) => n,
+            Iterations::Auto => {
+                let view = self.view();
+                let zoom = 4.0 / (view.to_y - view.from_y).abs();
+                let scale = (1.0 + zoom.log10().max(0.0)).powf(1.5);
+                ((765.0 * scale) as usize).min(MAX_AUTO_ITERATIONS)
+            }
+        }
+    }
+
+    /// The palette with the adjustments from the command line applied.
+    pub fn palette(&self) -> Palette {
+        self.styled(&self.palette)
+    }
+
+    /// `palette` with the coloring options applied.
+    fn styled(&self, palette: &Palette) -> Palette {
+        let mut palette = palette.clone();
+        if let Some(interpolation) = self.interpolation {
+            palette.interpolation = interpolation;
+        }
+        if let Some(mode) = self.palette_mode {
+            palette.mode = mode;
+        }
+        palette.offset = self.palette_offset;
+        palette.scale = self.palette_scale;
+        palette.repeat = self.palette_repeat;
+        palette
+    }
+
+    /// The bailout radius of `fractal`.
+    pub fn bailout(&self, fractal: &dyn Fractal) -> f64 {
+        match self.bailout {
+            Some(bailout) => bailout,
+            None => fractal.bailout().default_radius(self.smooth),
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        match self.threads {
+            Some(threads) => threads as usize,
+            None => thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// What draws the image: a shader for every pixel, or an orbit density.
+    pub fn renderer(&self) -> Result<Renderer, String> {
+        if let FractalKind::Attractor = self.fractal {
+            return Ok(Renderer::Drawing(Box::new(AttractorDensity {
+                attractor: Attractor::new(self.attractor, &self.params)?,
+                points: self.samples(100),
+                seed: self.seed,
+                palette: self.palette(),
+                view: self.bounds,
+            })));
+        }
+        if let FractalKind::Ifs = self.fractal {
+            return Ok(Renderer::Drawing(Box::new(ChaosGame {
+                ifs: self.ifs.clone(),
+                points: self.samples(100),
+                seed: self.seed,
+                palette: self.palette(),
+                view: self.bounds,
+            })));
+        }
+        if let FractalKind::Lsystem = self.fractal {
+            return Ok(Renderer::Drawing(Box::new(Turtle {
+                lines: self.lsystem().lines()?,
+                width: self.line_width,
+                palette: self.palette(),
+            })));
+        }
+        if let FractalKind::Flame = self.fractal {
+            let flame = &self.flame;
+            return Ok(Renderer::Drawing(Box::new(FlameRender {
+                flame: flame.clone(),
+                palette: match &flame.palette {
+                    Some(palette) => self.styled(palette),
+                    None => self.palette(),
+                },
+                points: self.samples(100),
+                seed: self.seed,
+                gamma: self.gamma.or(flame.gamma).unwrap_or(4.0),
+                vibrancy: self.vibrancy.or(flame.vibrancy).unwrap_or(1.0),
+                supersample: self
+                    .supersample
+                    .map(|n| n as usize)
+                    .or(flame.supersample)
+                    .unwrap_or(2),
+            })));
+        }
+        let Some(density) = self.density else {
+            return Ok(Renderer::Pixels(self.shader()?));
+        };
+        let limits = match density {
+            Density::Buddhabrot | Density::AntiBuddhabrot => vec![self.max_iterations()],
+            Density::Nebulabrot if self.limits.len() == 3 => self.limits.clone(),
+            Density::Nebulabrot => {
+                return Err("a Nebulabrot needs three --limits, for red, green and blue".to_string())
+            }
+        };
+        let fractal = self.fractal()?;
+        Ok(Renderer::Drawing(Box::new(Buddhabrot {
+            bailout: self.bailout(fractal.as_ref()),
+            fractal,
+            limits,
+            anti: matches!(density, Density::AntiBuddhabrot),
+            samples: self.samples(10),
+            seed: self.seed,
+            palette: self.palette(),
+        })))
+    }
+
+    /// The preset L-system with the changes from the command line.
+    fn lsystem(&self) -> LSystem {
+        let mut lsystem = self.lsystem.clone();
+        if let Some(axiom) = &self.axiom {
+            lsystem.axiom = axiom.clone();
+        }
+        for (symbol, replacement) in &self.rules {
+            lsystem.set_rule(*symbol, replacement.clone());
+        }
+        if let Some(angle) = self.angle {
+            lsystem.angle = angle;
+        }
+        if let Some(depth) = self.depth {
+            lsystem.depth = depth;
+        }
+        lsystem
+    }
+
+    /// The number of samples, by default `per_pixel` for every pixel.
+    fn samples(&self, per_pixel: usize) -> usize {
+        let (width, height) = self.size;
+        match self.samples {
+            Some(samples) => samples as usize,
+            None => per_pixel * width * height,
+        }
+    }
+
+    /// The fractal colored with the palette.
+    pub fn shader(&self) -> Result<Box<dyn Shader>, String> {
+        let method = match self.fractal {
+            FractalKind::Newton | FractalKind::Nova => Some(Method::Newton),
+            FractalKind::Halley => Some(Method::Halley),
+            FractalKind::Schroder => Some(Method::Schroder),
+            _ => None,
+        };
+        if let Some(method) = method {
+            let newton = self.newton(method)?;
+            if let FractalKind::Nova = self.fractal {
+                return Ok(Box::new(NovaShader {
+                    nova: Nova {
+                        newton,
+                        julia: self.julia.then_some(self.julia_c),
+                    },
+                    palette: self.palette(),
+                    max_iterations: self.max_iterations(),
+                    smooth: self.smooth,
+                }));
+            }
+            return Ok(Box::new(NewtonShader {
+                newton,
+                palette: self.palette(),
+                max_iterations: self.max_iterations(),
+            }));
+        }
+        if let FractalKind::Lyapunov = self.fractal {
+            return Ok(Box::new(Lyapunov {
+                sequence: self.sequence.clone(),
+                iterations: self.max_iterations(),
+                stable: self.palette(),
+                chaotic: self.styled(&self.chaotic_palette),
+            }));
+        }
+        let fractal = self.fractal()?;
+        let coloring = match self.coloring {
+            ColoringKind::Iterations => Coloring::Iterations,
+            ColoringKind::Biomorph => Coloring::Biomorph,
+            ColoringKind::Stalks => Coloring::Stalks {
+                width: self.stalk_width,
+            },
+            ColoringKind::Trap => Coloring::Trap(self.trap()?),
+            ColoringKind::TriangleInequality => Coloring::TriangleInequality,
+            ColoringKind::Stripes => Coloring::Stripes {
+                density: self.stripe_density,
+            },
+        };
+        let bailout = match coloring {
+            Coloring::Biomorph => self.bailout(&Biomorph(fractal.as_ref())),
+            // Averages are interpolated like smooth iteration counts.
+            Coloring::TriangleInequality | Coloring::Stripes { .. } => self
+                .bailout
+                .unwrap_or(fractal.bailout().default_radius(true)),
+            _ => self.bailout(fractal.as_ref()),
+        };
+        Ok(Box::new(EscapeTime {
+            fractal,
+            palette: self.palette(),
+            max_iterations: self.max_iterations(),
+            smooth: self.smooth,
+            bailout,
+            coloring,
+        }))
+    }
+
+    /// The orbit trap given by the --trap options.
+    fn trap(&self) -> Result<Trap, String> {
+        let angle = self.trap_angle.to_radians();
+        let shape = match self.trap {
+            TrapKind::Point => Shape::Point,
+            TrapKind::Line => Shape::Line { angle },
+            TrapKind::Cross => Shape::Cross { angle },
+            TrapKind::Circle => Shape::Circle {
+                radius: self.trap_radius,
+            },
+            TrapKind::Image => match &self.trap_image {
+                Some(picture) => Shape::Image(picture.clone()),
+                None => return Err("an image trap needs --trap-image".to_string()),
+            },
+        };
+        Ok(Trap {
+            shape,
+            center: self.trap_center,
+            size: self.trap_size,
+            mode: self.trap_mode,
+        })
+    }
+
+    fn fractal(&self) -> Result<Box<dyn Fractal>, String> {
+        Ok(match self.fractal {
+            FractalKind::Julia => Box::new(Julia { c: self.julia_c }),
+            FractalKind::Mandelbrot if self.julia => Box::new(Julia { c: self.julia_c }),
+            FractalKind::Mandelbrot => Box::new(Mandelbrot),
+            FractalKind::Multibrot => Box::new(Multibrot {
+                power: Exponent::new(self.power),
+                julia: self.julia.then_some(self.julia_c),
+            }),
+            FractalKind::BurningShip => self.variation(Variant::BurningShip),
+            FractalKind::Tricorn => self.variation(Variant::Tricorn),
+            FractalKind::Celtic => self.variation(Variant::Celtic),
+            FractalKind::Buffalo => self.variation(Variant::Buffalo),
+            FractalKind::Perpendicular => self.variation(Variant::Perpendicular),
+            FractalKind::Heart => self.variation(Variant::Heart),
+            FractalKind::Phoenix => self.recurrence(Recurrence::Phoenix { p: self.phoenix_p }),
+            FractalKind::MagnetI => self.recurrence(Recurrence::MagnetI),
+            FractalKind::MagnetII => self.recurrence(Recurrence::MagnetII),
+            FractalKind::Lambda => self.recurrence(Recurrence::Lambda),
+            FractalKind::Manowar => self.recurrence(Recurrence::Manowar),
+            FractalKind::Exp => self.transcendental(Transcendental::Exp),
+            FractalKind::Sin => self.transcendental(Transcendental::Sin),
+            FractalKind::Cos => self.transcendental(Transcendental::Cos),
+            FractalKind::Cosh => self.transcendental(Transcendental::Cosh),
+            FractalKind::QuadraticCosh => self.transcendental(Transcendental::QuadraticCosh),
+            FractalKind::Newton
+            | FractalKind::Halley
+            | FractalKind::Schroder
+            | FractalKind::Nova
+            | FractalKind::Lyapunov
+            | FractalKind::Attractor
+            | FractalKind::Ifs
+            | FractalKind::Flame
+            | FractalKind::Lsystem => {
+                let name = self.fractal.to_possible_value().unwrap();
+                return Err(format!(
+                    "the {} fractal has no escape-time orbits",
+                    name.get_name()
+                ));
+            }
+            FractalKind::Formula => {
+                let source = FormulaSource {
+                    step: &self.formula,
+                    init: &self.formula_init,
+                    c: &self.formula_c,
+                    bailout: &self.formula_bailout,
+                };
+                Box::new(Formula::new(&source, &self.params)?)
+            }
+        })
+    }
+
+    fn newton(&self, method: Method) -> Result<Newton, String> {
+        let mut newton = if self.roots.is_empty() {
+            let coefficients = match self.coefficients.as_slice() {
+                [] => &[Complex::ONE, Complex::ZERO, Complex::ZERO, -Complex::ONE][..],
+                coefficients => coefficients,
+            };
+            Newton::new(Polynomial::from_coefficients(coefficients), self.tolerance)
+        } else {
+            let mut newton = Newton::new(Polynomial::from_roots(&self.roots), self.tolerance);
+            newton.roots = self.roots.clone();
+            newton
+        };
+        if newton.polynomial.degree() == 0 {
+            return Err("the Newton polynomial must not be constant".to_string());
+        }
+        newton.method = method;
+        newton.relaxation = self.relaxation;
+        Ok(newton)
+    }
+
+    fn variation(&self, variant: Variant) -> Box<dyn Fractal> {
+        Box::new(Variation {
+            variant,
+            julia: self.julia.then_some(self.julia_c),
+        })
+    }
+
+    fn recurrence(&self, recurrence: Recurrence) -> Box<dyn Fractal> {
+        Box::new(RecurrenceSet {
+            recurrence,
+            julia: self.julia.then_some(self.julia_c),
+        })
+    }
+
+    fn transcendental(&self, function: Transcendental) -> Box<dyn Fractal> {
+        Box::new(TranscendentalSet {
+            function,
+            julia: self.julia.then_some(self.julia_c),
+        })
+    }
+}
+
+/// Upper bound for the automatic iteration limit.
+const MAX_AUTO_ITERATIONS: usize = 1_000_000;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Iterations {
+    Fixed(usize),
+    Auto,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum FractalKind {
+    Julia,
+    Mandelbrot,
+    /// `z^d + c` for the power given by --power.
+    Multibrot,
+    BurningShip,
+    /// Also known as the Mandelbar set.
+    Tricorn,
+    Celtic,
+    Buffalo,
+    Perpendicular,
+    Heart,
+    /// `z^2 + c + p*z_prev`, with `p` given by --p.
+    Phoenix,
+    MagnetI,
+    MagnetII,
+    /// `c*z*(1 - z)`.
+    Lambda,
+    /// `z^2 + z_prev + c`.
+    Manowar,
+    /// `c*exp(z)`, escaping when `re(z)` exceeds --bailout.
+    Exp,
+    /// `c*sin(z)`, escaping when `|im(z)|` exceeds --bailout.
+    Sin,
+    /// `c*cos(z)`, escaping when `|im(z)|` exceeds --bailout.
+    Cos,
+    /// `c*cosh(z)`, escaping when `|re(z)|` exceeds --bailout.
+    Cosh,
+    /// `z^2 + cosh(z) + c`, escaping when `|re(z)|` exceeds --bailout.
+    QuadraticCosh,
+    /// Defined by --formula and its companion options.
+    Formula,
+    /// Basins of Newton's method on the polynomial given by --roots or
+    /// --coefficients.
+    Newton,
+    /// Like `newton`, with Halley's method.
+    Halley,
+    /// Like `newton`, with Schröder's method for multiple roots.
+    Schroder,
+    /// `z - R*p(z)/p'(z) + c`, with the polynomial of `newton`.
+    Nova,
+    /// The logistic map with the rates `a` and `b` along the axes, applied
+    /// in the order of --sequence.
+    Lyapunov,
+    /// The density of a long orbit of the strange attractor given by
+    /// --attractor.
+    Attractor,
+    /// The chaos game on the iterated function system given by --ifs.
+    Ifs,
+    /// The fractal flame given by --flame.
+    Flame,
+    /// The curve drawn by the L-system given by --lsystem.
+    Lsystem,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum ColoringKind {
+    /// The number of iterations an orbit takes to escape.
+    Iterations,
+    /// Pickover's biomorphs: orbits stop once `|re z|` or `|im z|` passes
+    /// --bailout (10 by default) and are colored by which parts stayed
+    /// within it.
+    Biomorph,
+    /// Pickover's stalks: orbits passing within --stalk-width of the axes
+    /// are colored by how close they came.
+    Stalks,
+    /// How close orbits come to the trap given by the --trap options.
+    Trap,
+    /// The triangle inequality average (TIA) of the orbit.
+    TriangleInequality,
+    /// The stripe average of the orbit, with --stripe-density stripes.
+    Stripes,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum TrapKind {
+    /// A point at --trap-center.
+    Point,
+    /// A line through --trap-center at --trap-angle.
+    Line,
+    /// Two perpendicular lines through --trap-center at --trap-angle.
+    Cross,
+    /// A circle of --trap-radius around --trap-center.
+    Circle,
+    /// The picture in --trap-image, --trap-size wide on either side of
+    /// --trap-center. Orbits landing on it in hit mode take its colors.
+    Image,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum Density {
+    /// Orbits of the points that escape.
+    Buddhabrot,
+    /// Orbits of the points that stay bounded.
+    AntiBuddhabrot,
+    /// Buddhabrots for three iteration limits in red, green and blue.
+    Nebulabrot,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct View {
+    pub from_x: f64,
+    pub to_x: f64,
+    pub from_y: f64,
+    pub to_y: f64,
+}
+
+fn parse_number(s: &str) -> Result<f64, String> {
+    let value: f64 = s
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", s.trim()))?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("'{}' is not a finite number", s.trim()))
+    }
+}
+
+fn parse_complex(s: &str) -> Result<Complex, String> {
+    let z: Complex = s.parse().map_err(|err| format!("{err}"))?;
+    if z.re.is_finite() && z.im.is_finite() {
+        Ok(z)
+    } else {
+        Err(format!("'{}' is not a finite number", s.trim()))
+    }
+}
+
+fn parse_param(s: &str) -> Result<(String, Complex), String> {
+    let Some((name, value)) = s.split_once('=') else {
+        return Err("expected `NAME=VALUE`".to_string());
+    };
+    let name = name.trim();
+    let valid = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("'{name}' is not a valid parameter name"));
+    }
+    Ok((name.to_string(), parse_complex(value)?))
+}
+
+fn parse_unit(s: &str) -> Result<f64, String> {
+    let value = parse_number(s)?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err("the value must be between 0 and 1".to_string())
+    }
+}
+
+fn parse_positive(s: &str) -> Result<f64, String> {
+    let value = parse_number(s)?;
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err("the value must be greater than zero".to_string())
+    }
+}
+
+fn parse_zoom(s: &str) -> Result<f64, String> {
+    parse_positive(s).map_err(|_| "zoom must be greater than zero".to_string())
+}
+
+fn parse_iterations(s: &str) -> Result<Iterations, String> {
+    if s.trim().eq_ignore_ascii_case("auto") {
+        return Ok(Iterations::Auto);
+    }
+    match s.trim().parse() {
+        Ok(0) => Err("at least one iteration is required".to_string()),
+        Ok(n) => Ok(Iterations::Fixed(n)),
+        Err(_) => Err(format!("'{}' is neither a count nor `auto`", s.trim())),
+    }
+}
+
+fn parse_bounds(s: &str) -> Result<View, String> {
+    let values = s
+        .split(',')
+        .map(parse_number)
+        .collect::<Result<Vec<_>, _>>()?;
+    let [from_x, to_x, from_y, to_y] = values[..] else {
+        return Err("expected four numbers: from_x,to_x,from_y,to_y".to_string());
+    };
+    if from_x == to_x || from_y == to_y {
+        return Err("the region must not be empty".to_string());
+    }
+    Ok(View {
+        from_x,
+        to_x,
+        from_y,
+        to_y,
+    })
+}
+
+/// Most pixels an image may have, as many as in 16384x16384.
+const MAX_PIXELS: usize = 1 << 28;
+
+fn parse_size(s: &str) -> Result<(usize, usize), String> {
+    let parse = |v: &str| -> Result<usize, String> {
+        match v.trim().parse() {
+            Ok(0) => Err("the size must be at least 1 pixel".to_string()),
+            Ok(n) => Ok(n),
+            Err(_) => Err(format!("'{}' is not a valid pixel count", v.trim())),
+        }
+    };
+    let (width, height) = match s.split_once(['x', 'X']) {
+        Some((width, height)) => (parse(width)?, parse(height)?),
+        None => {
+            let side = parse(s)?;
+            (side, side)
+        }
+    };
+    match width.checked_mul(height) {
+        Some(pixels) if pixels <= MAX_PIXELS => Ok((width, height)),
+        _ => Err(format!(
+            "{width}x{height} is too large; an image may have at most {MAX_PIXELS} pixels"
+        )),
+    }
+}
+
+#[test]
+fn test_parsers() {
+    assert!(parse_complex("-0.4,0.5868") == Ok(Complex::new(-0.4, 0.5868)));
+    assert!(
+        parse_complex("1,2,3").is_err(),
+        "Three numbers were accepted."
+    );
+    assert!(
+        parse_complex("inf,0").is_err(),
+        "An infinite number was accepted."
+    );
+    assert!(parse_size("800x600") == Ok((800, 600)));
+    assert!(parse_size("512") == Ok((512, 512)));
+    assert!(parse_size("0x10").is_err(), "An empty image was accepted.");
+    assert!(parse_size("16384x16384").is_ok());
+    assert!(parse_size("16384x16385").is_err());
+    assert!(
+        parse_size("18446744073709551615x2").is_err(),
+        "An overflowing size was accepted."
+    );
+    assert!(parse_param("p=1-2i") == Ok(("p".to_string(), Complex::new(1.0, -2.0))));
+    assert!(
+        parse_param("2p=1").is_err(),
+        "An invalid name was accepted."
+    );
+    assert!(parse_iterations("100000") == Ok(Iterations::Fixed(100000)));
+    assert!(parse_iterations("auto") == Ok(Iterations::Auto));
+    assert!(
+        parse_iterations("0").is_err(),
+        "Zero iterations were accepted."
+    );
+    assert!(parse_zoom("-1").is_err(), "A negative zoom was accepted.");
+    assert!(
+        parse_bounds("0,0,1,2").is_err(),
+        "An empty region was accepted."
+    );
+}
+
+#[test]
+fn test_view() {
+    let cli = Cli::parse_from([
+        "", "render", "--center", "-1,0.5", "--zoom", "2", "--size", "200x100",
+    ]);
+    let Command::Render(args) = cli.command else {
+        panic!("Expected the render subcommand.");
+    };
+    assert!(
+        args.view()
+            == View {
+                from_x: -3.0,
+                to_x: 1.0,
+                from_y: -0.5,
+                to_y: 1.5
+            },
+        "The view does not match the center and zoom."
+    );
+}
+
+#[test]
+fn test_auto_iterations() {
+    let limit = |zoom: &str| {
+        let cli = Cli::parse_from(["", "render", "--zoom", zoom]);
+        let Command::Render(args) = cli.command else {
+            panic!("Expected the render subcommand.");
+        };
+        args.max_iterations()
+    };
+
+    assert!(limit("1") == 765, "The default limit has changed.");
+    assert!(
+        limit("1e6") > limit("1e3"),
+        "The limit does not grow with the zoom."
+    );
+    assert!(
+        limit("1e300") == MAX_AUTO_ITERATIONS,
+        "The limit is not capped."
+    );
+}
@@ -0,0 +1,437 @@
+use std::{
+    fmt::{self, Debug, Display},
+    ops::{self, Add},
+    str::FromStr,
+};
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+    pub const ONE: Complex = Complex { re: 1.0, im: 0.0 };
+    pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// The number with magnitude `r` and argument `theta`.
+    pub fn from_polar(r: f64, theta: f64) -> Self {
+        Self::new(r * theta.cos(), r * theta.sin())
+    }
+
+    pub fn abs(&self) -> f64 {
+        self.re.powi(2).add(self.im.powi(2)).sqrt()
+    }
+
+    /// The squared magnitude, which is cheaper than `abs`.
+    pub fn norm_sqr(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn conj(&self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    /// The angle from the positive real axis, in `(-pi, pi]`.
+    pub fn arg(&self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn exp(&self) -> Self {
+        Self::from_polar(self.re.exp(), self.im)
+    }
+
+    /// The principal natural logarithm.
+    pub fn ln(&self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+
+    /// The principal square root, with a non-negative real part.
+    pub fn sqrt(&self) -> Self {
+        let r = self.abs();
+        let re = ((r + self.re) / 2.0).sqrt();
+        let im = ((r - self.re) / 2.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    /// Integer power by repeated squaring.
+    pub fn powi(&self, n: i32) -> Self {
+        let mut base = if n < 0 { Self::ONE / *self } else { *self };
+        let mut n = n.unsigned_abs();
+        let mut result = Self::ONE;
+        while n > 0 {
+            if n & 1 == 1 {
+                result *= base;
+            }
+            base *= base;
+            n >>= 1;
+        }
+        result
+    }
+
+    /// Real power of the principal value, computed in polar form.
+    pub fn powf(&self, p: f64) -> Self {
+        if *self == Self::ZERO {
+            return if p == 0.0 { Self::ONE } else { Self::ZERO };
+        }
+        Self::from_polar(self.abs().powf(p), self.arg() * p)
+    }
+
+    /// Complex power of the principal value, `exp(w * ln(self))`.
+    pub fn powc(&self, w: Complex) -> Self {
+        if *self == Self::ZERO {
+            return if w == Self::ZERO {
+                Self::ONE
+            } else {
+                Self::ZERO
+            };
+        }
+        (w * self.ln()).exp()
+    }
+
+    pub fn sin(&self) -> Self {
+        Self::new(
+            self.re.sin() * self.im.cosh(),
+            self.re.cos() * self.im.sinh(),
+        )
+    }
+
+    pub fn cos(&self) -> Self {
+        Self::new(
+            self.re.cos() * self.im.cosh(),
+            -self.re.sin() * self.im.sinh(),
+        )
+    }
+
+    pub fn tan(&self) -> Self {
+        self.sin() / self.cos()
+    }
+
+    pub fn sinh(&self) -> Self {
+        Self::new(
+            self.re.sinh() * self.im.cos(),
+            self.re.cosh() * self.im.sin(),
+        )
+    }
+
+    pub fn cosh(&self) -> Self {
+        Self::new(
+            self.re.cosh() * self.im.cos(),
+            self.re.sinh() * self.im.sin(),
+        )
+    }
+
+    pub fn tanh(&self) -> Self {
+        self.sinh() / self.cosh()
+    }
+}
+
+impl ops::Add<Complex> for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Self::Output {
+        Self::Output::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl ops::Sub<Complex> for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Self::Output {
+        Self::Output::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl ops::Mul<Complex> for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Self::Output {
+        let a = self.re;
+        let b = self.im;
+        let c = rhs.re;
+        let d = rhs.im;
+        Self::Output::new(a * c - b * d, a * d + b * c)
+    }
+}
+
+impl ops::Div<Complex> for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: Complex) -> Self::Output {
+        let a = self.re;
+        let b = self.im;
+        let c = rhs.re;
+        let d = rhs.im;
+        let denominator = c * c + d * d;
+        Self::Output::new((a * c + b * d) / denominator, (b * c - a * d) / denominator)
+    }
+}
+
+impl ops::Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Self::Output {
+        Self::Output::new(-self.re, -self.im)
+    }
+}
+
+impl ops::Add<f64> for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: f64) -> Self::Output {
+        Self::Output::new(self.re + rhs, self.im)
+    }
+}
+
+impl ops::Sub<f64> for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: f64) -> Self::Output {
+        Self::Output::new(self.re - rhs, self.im)
+    }
+}
+
+impl ops::Mul<f64> for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Self::Output::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+impl ops::Div<f64> for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: f64) -> Self::Output {
+        Self::Output::new(self.re / rhs, self.im / rhs)
+    }
+}
+
+impl ops::Add<Complex> for f64 {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Self::Output {
+        rhs + self
+    }
+}
+
+impl ops::Sub<Complex> for f64 {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Self::Output {
+        Self::Output::new(self - rhs.re, -rhs.im)
+    }
+}
+
+impl ops::Mul<Complex> for f64 {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl ops::Div<Complex> for f64 {
+    type Output = Complex;
+
+    fn div(self, rhs: Complex) -> Self::Output {
+        Complex::new(self, 0.0) / rhs
+    }
+}
+
+macro_rules! assign_ops {
+    ($($trait:ident $method:ident $op:tt),*) => {
+        $(
+            impl ops::$trait<Complex> for Complex {
+                fn $method(&mut self, rhs: Complex) {
+                    *self = *self $op rhs;
+                }
+            }
+
+            impl ops::$trait<f64> for Complex {
+                fn $method(&mut self, rhs: f64) {
+                    *self = *self $op rhs;
+                }
+            }
+        )*
+    };
+}
+
+assign_ops!(AddAssign add_assign +, SubAssign sub_assign -, MulAssign mul_assign *, DivAssign div_assign /);
+
+impl Debug for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("").field(&self.re).field(&self.im).finish()
+    }
+}
+
+/// Formats as `a+bi` or `a-bi`.
+impl Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.im.is_sign_negative() { '-' } else { '+' };
+        write!(f, "{}{sign}{}i", self.re, self.im.abs())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseComplexError(String);
+
+impl Display for ParseComplexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' is not a complex number; use `re,im` or `a+bi`",
+            self.0
+        )
+    }
+}
+
+/// Parses `re,im`, or `a+bi` where either part may be left out: `1.5`,
+/// `-2i`, `i` and `1e-3-0.5i` are all accepted.
+impl FromStr for Complex {
+    type Err = ParseComplexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseComplexError(s.trim().to_string());
+        let s = s.trim();
+
+        if let Some((re, im)) = s.split_once(',') {
+            let re = re.trim().parse().map_err(|_| error())?;
+            let im = im.trim().parse().map_err(|_| error())?;
+            return Ok(Self::new(re, im));
+        }
+
+        let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        let Some(imaginary) = s.strip_suffix(['i', 'j']) else {
+            return s.parse().map(|re| Self::new(re, 0.0)).map_err(|_| error());
+        };
+
+        // The imaginary part starts at the last sign that is not part of an
+        // exponent.
+        let split = imaginary
+            .char_indices()
+            .rev()
+            .find(|&(i, c)| (c == '+' || c == '-') && !imaginary[..i].ends_with(['e', 'E']))
+            .map_or(0, |(i, _)| i);
+        let (re, im) = imaginary.split_at(split);
+        let re = if re.is_empty() {
+            0.0
+        } else {
+            re.parse().map_err(|_| error())?
+        };
+        let im = match im {
+            "" | "+" => 1.0,
+            "-" => -1.0,
+            im => im.parse().map_err(|_| error())?,
+        };
+        Ok(Self::new(re, im))
+    }
+}
+
+#[cfg(test)]
+fn close(a: Complex, b: Complex) -> bool {
+    (a - b).abs() < 1e-12 * (1.0 + b.abs())
+}
+
+#[test]
+fn test_complex() {
+    let a: f64 = 1.0;
+    let b: f64 = 2.0;
+    let c: f64 = -1.0;
+    let d: f64 = 3.0;
+
+    let z1: Complex = Complex { re: a, im: b };
+    let z2: Complex = Complex::new(c, d);
+
+    assert!(z1 + z2 == Complex::new(a + c, b + d));
+    assert!(z1 - z2 == Complex::new(a - c, b - d));
+    assert!(z1 * z2 == Complex::new(a * c - b * d, a * d + b * c));
+    assert!(
+        close((z1 / z2) * z2, z1),
+        "Division does not undo multiplication."
+    );
+    assert!(-z1 == Complex::new(-a, -b));
+
+    assert!(z1 + 2.0 == Complex::new(a + 2.0, b));
+    assert!(2.0 - z1 == Complex::new(2.0 - a, -b));
+    assert!(z1 * 2.0 == 2.0 * z1 && z1 * 2.0 == Complex::new(2.0 * a, 2.0 * b));
+    assert!(close(1.0 / z1, Complex::ONE / z1));
+
+    let mut z = z1;
+    z += z2;
+    z -= 1.0;
+    z *= z2;
+    z /= 2.0;
+    assert!(
+        z == (z1 + z2 - 1.0) * z2 / 2.0,
+        "Assignment operators differ."
+    );
+
+    assert!(z1.conj() == Complex::new(a, -b));
+    assert!(z1.norm_sqr() == a * a + b * b);
+    assert!(Complex::new(-1.0, 0.0).arg() == std::f64::consts::PI);
+    assert!(Complex::I * Complex::I == -Complex::ONE);
+}
+
+#[test]
+fn test_complex_functions() {
+    use std::f64::consts::PI;
+
+    let z = Complex::new(0.7, -1.3);
+
+    assert!(close((Complex::I * PI).exp(), -Complex::ONE));
+    assert!(close(z.ln().exp(), z), "ln is not the inverse of exp.");
+    assert!(close(z.sqrt() * z.sqrt(), z));
+    assert!(z.sqrt().re >= 0.0, "sqrt is not the principal root.");
+    assert!(close(
+        Complex::new(-4.0, 0.0).sqrt(),
+        Complex::new(0.0, 2.0)
+    ));
+
+    assert!(close(z.powi(3), z * z * z));
+    assert!(close(z.powi(-2), Complex::ONE / (z * z)));
+    assert!(z.powi(0) == Complex::ONE);
+    assert!(close(z.powf(3.0), z * z * z));
+    assert!(close(z.powf(0.5), z.sqrt()));
+    assert!(close(z.powc(Complex::new(2.0, 0.0)), z * z));
+    assert!(close(
+        Complex::I.powc(Complex::I),
+        Complex::new((-PI / 2.0).exp(), 0.0)
+    ));
+    assert!(Complex::ZERO.powf(2.0) == Complex::ZERO);
+
+    let one = z.sin() * z.sin() + z.cos() * z.cos();
+    assert!(close(one, Complex::ONE), "sin^2 + cos^2 != 1");
+    let one = z.cosh() * z.cosh() - z.sinh() * z.sinh();
+    assert!(close(one, Complex::ONE), "cosh^2 - sinh^2 != 1");
+    assert!(close(z.tan(), z.sin() / z.cos()));
+    assert!(close(z.tanh(), z.sinh() / z.cosh()));
+    assert!(close((Complex::I * z).sinh(), Complex::I * z.sin()));
+}
+
+#[test]
+fn test_complex_text() {
+    assert!(Complex::new(1.5, -2.0).to_string() == "1.5-2i");
+    assert!(Complex::new(0.0, 0.25).to_string() == "0+0.25i");
+
+    let parse = |s: &str| s.parse::<Complex>();
+    assert!(parse("1.5-2i") == Ok(Complex::new(1.5, -2.0)));
+    assert!(parse("-0.4, 0.5868") == Ok(Complex::new(-0.4, 0.5868)));
+    assert!(parse("-i") == Ok(Complex::new(0.0, -1.0)));
+    assert!(parse("3") == Ok(Complex::new(3.0, 0.0)));
+    assert!(parse("2.5i") == Ok(Complex::new(0.0, 2.5)));
+    assert!(parse("1e-3 + 2e+2i") == Ok(Complex::new(1e-3, 2e2)));
+    assert!(parse("1+2").is_err());
+    assert!(parse("abc").is_err());
+
+    let z = Complex::new(-0.123, 4.5e-7);
+    assert!(
+        parse(&z.to_string()) == Ok(z),
+        "Display does not round-trip."
+    );
+}
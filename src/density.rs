@@ -0,0 +1,373 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::cli::View;
+use crate::fractal::Fractal;
+use crate::palette::Palette;
+use crate::random::Rng;
+use crate::{map, Complex, Draw, Image, Settings};
+
+/// Hit counts for every pixel of an image.
+pub struct Histogram {
+    width: usize,
+    height: usize,
+    view: View,
+    pub counts: Vec<u32>,
+}
+
+impl Histogram {
+    pub fn new(width: usize, height: usize, view: View) -> Self {
+        Self {
+            width,
+            height,
+            view,
+            counts: vec![0; width * height],
+        }
+    }
+
+    /// A histogram of a drawing in world coordinates, whose y axis points up
+    /// the image instead of down like the rows of the complex plane.
+    pub fn upright(width: usize, height: usize, view: View) -> Self {
+        let flipped = View {
+            from_y: view.to_y,
+            to_y: view.from_y,
+            ..view
+        };
+        Self::new(width, height, flipped)
+    }
+
+    /// Counts a hit on the pixel containing `z`, if there is one.
+    pub fn plot(&mut self, z: Complex) {
+        let View {
+            from_x,
+            to_x,
+            from_y,
+            to_y,
+        } = self.view;
+        let x = map(z.re, from_x, to_x, 0.0, self.width as f64);
+        let y = map(z.im, from_y, to_y, 0.0, self.height as f64);
+        if x >= 0.0 && y >= 0.0 && x < self.width as f64 && y < self.height as f64 {
+            let count = &mut self.counts[y as usize * self.width + x as usize];
+            *count = count.saturating_add(1);
+        }
+    }
+
+    pub fn max(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Colors every pixel by the logarithm of its hit count, which keeps
+    /// the faint parts visible next to the dense ones.
+    pub fn log_image(&self, palette: &Palette) -> Image {
+        let mut img = Image::new(self.width, self.height);
+        let scale = 1.0 / (1.0 + self.max().max(1) as f64).ln();
+        for (pixel, &count) in img.data.iter_mut().zip(&self.counts) {
+            let (r, g, b) = palette.color((1.0 + count as f64).ln() * scale);
+            *pixel = Image::pack(r, g, b);
+        }
+        img
+    }
+}
+
+/// Room left around a fitted drawing, as a fraction of the image size.
+const MARGIN: f64 = 0.05;
+
+/// The region that holds the finite `points` with a margin, in the
+/// proportions of a `width` by `height` image, or `[-2, 2]` if there are
+/// none.
+pub fn fit(points: impl IntoIterator<Item = Complex>, width: usize, height: usize) -> View {
+    let (mut min, mut max) = (
+        Complex::new(f64::INFINITY, f64::INFINITY),
+        Complex::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+    );
+    for p in points {
+        if p.re.is_finite() && p.im.is_finite() {
+            min = Complex::new(min.re.min(p.re), min.im.min(p.im));
+            max = Complex::new(max.re.max(p.re), max.im.max(p.im));
+        }
+    }
+    if !(min.re.is_finite() && min.im.is_finite()) {
+        min = Complex::new(-2.0, -2.0);
+        max = Complex::new(2.0, 2.0);
+    }
+
+    let middle = (min + max) / 2.0;
+    let size = (max - min) / (1.0 - 2.0 * MARGIN);
+    let aspect = width as f64 / height as f64;
+    let half_height = (size.im.max(1e-9)).max(size.re.max(1e-9) / aspect) / 2.0;
+    let half_width = half_height * aspect;
+    View {
+        from_x: middle.re - half_width,
+        to_x: middle.re + half_width,
+        from_y: middle.im - half_height,
+        to_y: middle.im + half_height,
+    }
+}
+
+/// What a sampled render accumulates into. Every thread fills its own,
+/// and they are added up at the end.
+pub trait Buffer: Send {
+    fn merge(&mut self, other: &Self);
+}
+
+impl Buffer for Histogram {
+    fn merge(&mut self, other: &Histogram) {
+        for (count, &hits) in self.counts.iter_mut().zip(&other.counts) {
+            *count = count.saturating_add(hits);
+        }
+    }
+}
+
+impl Buffer for Vec<Histogram> {
+    fn merge(&mut self, other: &Vec<Histogram>) {
+        for (histogram, part) in self.iter_mut().zip(other) {
+            histogram.merge(part);
+        }
+    }
+}
+
+/// Runs `work` on every chunk of a sampled render, with chunks handed out
+/// to `threads` workers in turn, and adds up their buffers, which start out
+/// as `empty()`. Every chunk should draw from its own random stream, so the
+/// sum does not depend on the number of threads.
+pub fn accumulate<B, E, F>(threads: usize, chunks: usize, empty: E, work: F) -> B
+where
+    B: Buffer,
+    E: Fn() -> B + Sync,
+    F: Fn(usize, &mut B) + Sync,
+{
+    let threads = threads.clamp(1, chunks.max(1));
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+
+    print!("0%");
+    let partials: Vec<B> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut partial = empty();
+                    loop {
+                        let chunk = next.fetch_add(1, Ordering::Relaxed);
+                        if chunk >= chunks {
+                            break partial;
+                        }
+                        work(chunk, &mut partial);
+                        let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                        print!("\r{}%", 100 * done / chunks);
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect()
+    });
+
+    let mut sum = empty();
+    for partial in &partials {
+        sum.merge(partial);
+    }
+    sum
+}
+
+/// Samples of a Buddhabrot drawn from one random stream.
+const CHUNK: usize = 1 << 12;
+
+/// Half the side of the square points are sampled from, which holds the
+/// Mandelbrot set whatever the bailout radius.
+const SAMPLE_RADIUS: f64 = 2.0;
+
+/// Draws the orbits of random points as a density image: the Buddhabrot
+/// for the Mandelbrot set. Points are sampled from the square
+/// `[-2, 2] x [-2, 2]`.
+pub struct Buddhabrot {
+    pub fractal: Box<dyn Fractal>,
+    /// Iteration limit for every channel: one for an image colored with the
+    /// palette, or three for the red, green and blue of a Nebulabrot. Every
+    /// thread buffers an orbit of up to the highest limit, at 16 bytes a
+    /// step, until it knows whether the orbit escaped.
+    pub limits: Vec<usize>,
+    /// Draw the orbits that stay bounded instead of those that escape.
+    pub anti: bool,
+    pub samples: usize,
+    pub seed: u64,
+    pub bailout: f64,
+    pub palette: Palette,
+}
+
+impl Buddhabrot {
+    /// The hit counts of every channel.
+    pub fn accumulate(&self, settings: &Settings) -> Vec<Histogram> {
+        let chunks = self.samples.div_ceil(CHUNK);
+        let empty = || -> Vec<Histogram> {
+            self.limits
+                .iter()
+                .map(|_| Histogram::new(settings.width, settings.height, settings.view))
+                .collect()
+        };
+        accumulate(settings.threads, chunks, empty, |chunk, histograms| {
+            let mut rng = Rng::stream(self.seed, chunk as u64);
+            let mut orbit = Vec::new();
+            for _ in chunk * CHUNK..self.samples.min((chunk + 1) * CHUNK) {
+                self.sample(&mut rng, &mut orbit, histograms);
+            }
+        })
+    }
+
+    /// Follows the orbit of one random point up to the highest limit and
+    /// plots it into every channel whose limit it qualifies for.
+    fn sample(&self, rng: &mut Rng, orbit: &mut Vec<Complex>, histograms: &mut [Histogram]) {
+        let bailout_sqr = self.bailout * self.bailout;
+        let r = SAMPLE_RADIUS;
+        let pixel = Complex::new(rng.range(-r, r), rng.range(-r, r));
+        let max_limit = self.limits.iter().copied().max().unwrap_or(0);
+
+        // One step past the highest limit tells whether the orbit escaped
+        // on its last allowed step.
+        let steps = max_limit.saturating_add(1);
+        orbit.clear();
+        let escape = self
+            .fractal
+            .escape_with(pixel, bailout_sqr, steps, &mut |state| orbit.push(state.z));
+        let escaped = escape.escaped(steps);
+
+        for (histogram, &limit) in histograms.iter_mut().zip(&self.limits) {
+            let escaped = escaped && orbit.len() <= limit;
+            if escaped != self.anti {
+                for &z in &orbit[..orbit.len().min(limit)] {
+                    histogram.plot(z);
+                }
+            }
+        }
+    }
+}
+
+impl Draw for Buddhabrot {
+    fn draw(&self, settings: &Settings) -> Image {
+        let histograms = self.accumulate(settings);
+        let mut img = Image::new(settings.width, settings.height);
+
+        // The square root brings out the faint orbits next to the bright
+        // ones.
+        let scales: Vec<f64> = histograms
+            .iter()
+            .map(|h| 1.0 / h.max().max(1) as f64)
+            .collect();
+        for (i, pixel) in img.data.iter_mut().enumerate() {
+            let level =
+                |channel: usize| (histograms[channel].counts[i] as f64 * scales[channel]).sqrt();
+            let (r, g, b) = match histograms.len() {
+                1 => self.palette.color(level(0)),
+                _ => {
+                    let byte = |channel| (255.0 * level(channel)).round() as u8;
+                    (byte(0), byte(1), byte(2))
+                }
+            };
+            *pixel = Image::pack(r, g, b);
+        }
+        img
+    }
+}
+
+#[cfg(test)]
+pub fn test_settings(threads: usize) -> Settings {
+    Settings {
+        view: View {
+            from_x: -2.0,
+            to_x: 2.0,
+            from_y: -2.0,
+            to_y: 2.0,
+        },
+        width: 32,
+        height: 32,
+        threads,
+    }
+}
+
+#[test]
+fn test_histogram() {
+    let settings = test_settings(1);
+    let mut histogram = Histogram::new(4, 2, settings.view);
+    histogram.plot(Complex::new(-1.5, -1.5));
+    histogram.plot(Complex::new(1.9, 1.9));
+    histogram.plot(Complex::new(1.9, 1.9));
+    histogram.plot(Complex::new(2.0, 0.0));
+    histogram.plot(Complex::new(f64::NAN, 0.0));
+    assert!(histogram.counts == [1, 0, 0, 0, 0, 0, 0, 2]);
+    assert!(histogram.max() == 2);
+
+    let mut upright = Histogram::upright(4, 2, settings.view);
+    upright.plot(Complex::new(-1.5, -1.5));
+    upright.plot(Complex::new(1.9, 1.9));
+    assert!(upright.counts == [0, 0, 0, 1, 1, 0, 0, 0]);
+}
+
+#[test]
+fn test_buddhabrot() {
+    use crate::formula::{Formula, FormulaSource};
+    use crate::fractal::{Julia, Mandelbrot};
+
+    let buddhabrot = |limits: Vec<usize>, anti| Buddhabrot {
+        fractal: Box::new(Mandelbrot),
+        limits,
+        anti,
+        samples: 3 * CHUNK + 5,
+        seed: 42,
+        bailout: 2.0,
+        palette: Palette::builtin("gray").unwrap(),
+    };
+
+    let nebulabrot = buddhabrot(vec![100, 50, 20], false);
+    let one = nebulabrot.accumulate(&test_settings(1));
+    let many = nebulabrot.accumulate(&test_settings(5));
+    for (a, b) in one.iter().zip(&many) {
+        assert!(a.counts == b.counts, "The threads changed the result.");
+    }
+    let total = |h: &Histogram| h.counts.iter().map(|&n| n as u64).sum::<u64>();
+    assert!(
+        total(&one[2]) > 0 && total(&one[1]) > total(&one[2]),
+        "Higher limits should draw more."
+    );
+
+    // Bounded orbits stay in the Mandelbrot set, away from its outside.
+    let anti = buddhabrot(vec![50], true).accumulate(&test_settings(2));
+    let outside = Complex::new(1.0, 1.0);
+    let x = map(outside.re, -2.0, 2.0, 0.0, 32.0) as usize;
+    let y = map(outside.im, -2.0, 2.0, 0.0, 32.0) as usize;
+    assert!(anti[0].counts[y * 32 + x] == 0);
+    assert!(total(&anti[0]) > 0);
+
+    let img = buddhabrot(vec![50], false).draw(&test_settings(2));
+    assert!(img.data.iter().any(|&pixel| pixel != 0));
+
+    // A larger bailout radius follows the same samples a little further,
+    // instead of spreading them over its own square.
+    let wide = Buddhabrot {
+        bailout: 256.0,
+        ..buddhabrot(vec![50], false)
+    };
+    let narrow = buddhabrot(vec![50], false).accumulate(&test_settings(2));
+    assert!(
+        total(&wide.accumulate(&test_settings(2))[0]) > total(&narrow[0]) / 2,
+        "The samples depend on the bailout radius."
+    );
+
+    // A formula is sampled with its own escape test.
+    let source = FormulaSource::default();
+    let formula = Buddhabrot {
+        fractal: Box::new(Formula::new(&source, &[]).unwrap()),
+        ..buddhabrot(vec![50], false)
+    };
+    let expected = buddhabrot(vec![50], false).accumulate(&test_settings(2));
+    assert!(formula.accumulate(&test_settings(2))[0].counts == expected[0].counts);
+
+    // Almost every orbit of a dust-like Julia set escapes, so even the
+    // largest limit is never reached.
+    let unlimited = Buddhabrot {
+        fractal: Box::new(Julia { c: Complex::ONE }),
+        samples: 100,
+        ..buddhabrot(vec![usize::MAX], false)
+    };
+    assert!(total(&unlimited.accumulate(&test_settings(1))[0]) > 0);
+}
@@ -0,0 +1,656 @@
+mod format;
+
+use std::f64::consts::PI;
+
+use crate::cli::View;
+use crate::density::{self, Buffer};
+use crate::ifs::Affine;
+use crate::palette::Palette;
+use crate::random::Rng;
+use crate::{map, Complex, Draw, Image, Settings};
+
+pub use format::parse_flame;
+
+/// The nonlinear functions of fractal flames, named and defined as in
+/// flam3. With `r = |p|` and `theta = atan2(x, y)`:
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FlameVariation {
+    /// `(x, y)`
+    Linear,
+    /// `(sin x, sin y)`
+    Sinusoidal,
+    /// `(x, y) / r^2`
+    Spherical,
+    /// `(x sin r^2 - y cos r^2, x cos r^2 + y sin r^2)`
+    Swirl,
+    /// `((x - y)(x + y), 2xy) / r`
+    Horseshoe,
+    /// `(theta / pi, r - 1)`
+    Polar,
+    /// `r (sin(theta + r), cos(theta - r))`
+    Handkerchief,
+    /// `r (sin(theta r), -cos(theta r))`
+    Heart,
+    /// `theta / pi (sin(pi r), cos(pi r))`
+    Disc,
+    /// `(cos theta + sin r, sin theta - cos r) / r`
+    Spiral,
+    /// `(sin theta / r, r cos theta)`
+    Hyperbolic,
+    /// `(sin theta cos r, cos theta sin r)`
+    Diamond,
+    /// `r (p0^3 + p1^3, p0^3 - p1^3)` with `p0 = sin(theta + r)` and
+    /// `p1 = cos(theta - r)`
+    Ex,
+    /// `sqrt(r) (cos(phi / 2 + w), sin(phi / 2 + w))` with `phi = atan2(y, x)`
+    /// and `w` randomly 0 or pi.
+    Julia,
+    /// `x` doubled where negative, `y` halved where negative.
+    Bent,
+    /// `(x + b sin(y / e^2), y + d sin(x / f^2))` with the coefficients of the
+    /// affine transform.
+    Waves,
+    /// `2 / (r + 1) (y, x)`
+    Fisheye,
+    /// `(x + e sin(tan 3y), y + f sin(tan 3x))` with the affine coefficients.
+    Popcorn,
+    /// `e^(x - 1) (cos(pi y), sin(pi y))`
+    Exponential,
+    /// `r^sin(theta) (cos theta, sin theta)`
+    Power,
+    /// `(cos(pi x) cosh y, -sin(pi x) sinh y)`
+    Cosine,
+    /// `2 / (r + 1) (x, y)`
+    Eyefish,
+    /// `4 / (r^2 + 4) (x, y)`
+    Bubble,
+    /// `(sin x, y)`
+    Cylinder,
+    /// `(sin x / cos y, tan y)`
+    Tangent,
+    /// `(x, y) / |x^2 - y^2|`
+    Cross,
+}
+
+impl FlameVariation {
+    pub const ALL: [FlameVariation; 26] = [
+        FlameVariation::Linear,
+        FlameVariation::Sinusoidal,
+        FlameVariation::Spherical,
+        FlameVariation::Swirl,
+        FlameVariation::Horseshoe,
+        FlameVariation::Polar,
+        FlameVariation::Handkerchief,
+        FlameVariation::Heart,
+        FlameVariation::Disc,
+        FlameVariation::Spiral,
+        FlameVariation::Hyperbolic,
+        FlameVariation::Diamond,
+        FlameVariation::Ex,
+        FlameVariation::Julia,
+        FlameVariation::Bent,
+        FlameVariation::Waves,
+        FlameVariation::Fisheye,
+        FlameVariation::Popcorn,
+        FlameVariation::Exponential,
+        FlameVariation::Power,
+        FlameVariation::Cosine,
+        FlameVariation::Eyefish,
+        FlameVariation::Bubble,
+        FlameVariation::Cylinder,
+        FlameVariation::Tangent,
+        FlameVariation::Cross,
+    ];
+
+    /// The name used in `.flame` files.
+    pub fn name(self) -> &'static str {
+        match self {
+            FlameVariation::Linear => "linear",
+            FlameVariation::Sinusoidal => "sinusoidal",
+            FlameVariation::Spherical => "spherical",
+            FlameVariation::Swirl => "swirl",
+            FlameVariation::Horseshoe => "horseshoe",
+            FlameVariation::Polar => "polar",
+            FlameVariation::Handkerchief => "handkerchief",
+            FlameVariation::Heart => "heart",
+            FlameVariation::Disc => "disc",
+            FlameVariation::Spiral => "spiral",
+            FlameVariation::Hyperbolic => "hyperbolic",
+            FlameVariation::Diamond => "diamond",
+            FlameVariation::Ex => "ex",
+            FlameVariation::Julia => "julia",
+            FlameVariation::Bent => "bent",
+            FlameVariation::Waves => "waves",
+            FlameVariation::Fisheye => "fisheye",
+            FlameVariation::Popcorn => "popcorn",
+            FlameVariation::Exponential => "exponential",
+            FlameVariation::Power => "power",
+            FlameVariation::Cosine => "cosine",
+            FlameVariation::Eyefish => "eyefish",
+            FlameVariation::Bubble => "bubble",
+            FlameVariation::Cylinder => "cylinder",
+            FlameVariation::Tangent => "tangent",
+            FlameVariation::Cross => "cross",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.name() == name)
+    }
+
+    /// The variation at `p`, the point after the affine transform `affine`.
+    pub fn apply(self, p: Complex, affine: &Affine, rng: &mut Rng) -> Complex {
+        /// Keeps divisions by `r` finite at the origin.
+        const EPS: f64 = 1e-10;
+
+        let (x, y) = (p.re, p.im);
+        let r2 = x * x + y * y;
+        let r = r2.sqrt();
+        let theta = x.atan2(y);
+        let (re, im) = match self {
+            FlameVariation::Linear => (x, y),
+            FlameVariation::Sinusoidal => (x.sin(), y.sin()),
+            FlameVariation::Spherical => (x / (r2 + EPS), y / (r2 + EPS)),
+            FlameVariation::Swirl => {
+                let (sin, cos) = r2.sin_cos();
+                (x * sin - y * cos, x * cos + y * sin)
+            }
+            FlameVariation::Horseshoe => ((x - y) * (x + y) / (r + EPS), 2.0 * x * y / (r + EPS)),
+            FlameVariation::Polar => (theta / PI, r - 1.0),
+            FlameVariation::Handkerchief => (r * (theta + r).sin(), r * (theta - r).cos()),
+            FlameVariation::Heart => (r * (theta * r).sin(), -r * (theta * r).cos()),
+            FlameVariation::Disc => {
+                let (sin, cos) = (PI * r).sin_cos();
+                (theta / PI * sin, theta / PI * cos)
+            }
+            FlameVariation::Spiral => (
+                (theta.cos() + r.sin()) / (r + EPS),
+                (theta.sin() - r.cos()) / (r + EPS),
+            ),
+            FlameVariation::Hyperbolic => (theta.sin() / (r + EPS), r * theta.cos()),
+            FlameVariation::Diamond => (theta.sin() * r.cos(), theta.cos() * r.sin()),
+            FlameVariation::Ex => {
+                let p0 = (theta + r).sin().powi(3);
+                let p1 = (theta - r).cos().powi(3);
+                (r * (p0 + p1), r * (p0 - p1))
+            }
+            FlameVariation::Julia => {
+                let w = if rng.next_u64() & 1 == 0 { 0.0 } else { PI };
+                let (sin, cos) = (y.atan2(x) / 2.0 + w).sin_cos();
+                (r.sqrt() * cos, r.sqrt() * sin)
+            }
+            FlameVariation::Bent => (
+                if x < 0.0 { 2.0 * x } else { x },
+                if y < 0.0 { y / 2.0 } else { y },
+            ),
+            FlameVariation::Waves => (
+                x + affine.b * (y / (affine.e * affine.e + EPS)).sin(),
+                y + affine.d * (x / (affine.f * affine.f + EPS)).sin(),
+            ),
+            FlameVariation::Fisheye => (2.0 * y / (r + 1.0), 2.0 * x / (r + 1.0)),
+            FlameVariation::Popcorn => (
+                x + affine.e * (3.0 * y).tan().sin(),
+                y + affine.f * (3.0 * x).tan().sin(),
+            ),
+            FlameVariation::Exponential => {
+                let (sin, cos) = (PI * y).sin_cos();
+                ((x - 1.0).exp() * cos, (x - 1.0).exp() * sin)
+            }
+            FlameVariation::Power => {
+                let scale = r.powf(theta.sin());
+                (scale * theta.cos(), scale * theta.sin())
+            }
+            FlameVariation::Cosine => ((PI * x).cos() * y.cosh(), -(PI * x).sin() * y.sinh()),
+            FlameVariation::Eyefish => (2.0 * x / (r + 1.0), 2.0 * y / (r + 1.0)),
+            FlameVariation::Bubble => (4.0 * x / (r2 + 4.0), 4.0 * y / (r2 + 4.0)),
+            FlameVariation::Cylinder => (x.sin(), y),
+            FlameVariation::Tangent => (x.sin() / y.cos(), y.tan()),
+            FlameVariation::Cross => {
+                let scale = 1.0 / ((x * x - y * y).abs() + EPS);
+                (x * scale, y * scale)
+            }
+        };
+        Complex::new(re, im)
+    }
+}
+
+/// One transform of a flame: an affine map, a blend of variations and an
+/// optional affine map after them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Xform {
+    /// How often the transform is picked, relative to the others.
+    pub weight: f64,
+    pub affine: Affine,
+    pub variations: Vec<(FlameVariation, f64)>,
+    pub post: Option<Affine>,
+    /// Position in the palette that points move towards.
+    pub color: f64,
+    /// How far they move each time: 0 keeps their color, 1 takes `color`.
+    pub color_speed: f64,
+}
+
+impl Xform {
+    pub fn apply(&self, p: Complex, rng: &mut Rng) -> Complex {
+        let q = self.affine.apply(p);
+        let mut sum = Complex::ZERO;
+        for &(variation, weight) in &self.variations {
+            sum += variation.apply(q, &self.affine, rng) * weight;
+        }
+        match &self.post {
+            Some(post) => post.apply(sum),
+            None => sum,
+        }
+    }
+
+    /// Moves the color index `c` towards this transform's color.
+    pub fn blend(&self, c: f64) -> f64 {
+        c + (self.color - c) * self.color_speed
+    }
+}
+
+/// The view of a `.flame` file.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    pub center: Complex,
+    /// Width of the view.
+    pub width: f64,
+    /// Turn of the image in radians.
+    pub rotate: f64,
+}
+
+/// A fractal flame, with the settings a `.flame` file may carry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Flame {
+    pub xforms: Vec<Xform>,
+    /// Applied to every point before it is drawn, but not fed back.
+    pub final_xform: Option<Xform>,
+    pub palette: Option<Palette>,
+    pub camera: Option<Camera>,
+    pub gamma: Option<f64>,
+    pub vibrancy: Option<f64>,
+    pub supersample: Option<usize>,
+}
+
+pub const BUILTINS: [&str; 3] = ["julia", "spherical", "swirl"];
+
+impl Flame {
+    fn new(xforms: Vec<Xform>) -> Self {
+        Self {
+            xforms,
+            final_xform: None,
+            palette: None,
+            camera: None,
+            gamma: None,
+            vibrancy: None,
+            supersample: None,
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        use FlameVariation::*;
+        let xform =
+            |color, [a, b, c, d, e, f]: [f64; 6], variations: &[(FlameVariation, f64)]| Xform {
+                weight: 1.0,
+                affine: Affine { a, b, c, d, e, f },
+                variations: variations.to_vec(),
+                post: None,
+                color,
+                color_speed: 0.5,
+            };
+        let xforms = match name {
+            // Sierpinski's triangle bent by the spherical variation.
+            "spherical" => vec![
+                xform(
+                    0.0,
+                    [0.5, 0.0, 0.0, 0.5, -0.5, -0.3],
+                    &[(Spherical, 0.7), (Linear, 0.3)],
+                ),
+                xform(
+                    0.5,
+                    [0.5, 0.0, 0.0, 0.5, 0.5, -0.3],
+                    &[(Spherical, 0.7), (Linear, 0.3)],
+                ),
+                xform(
+                    1.0,
+                    [0.5, 0.0, 0.0, 0.5, 0.0, 0.6],
+                    &[(Spherical, 0.7), (Linear, 0.3)],
+                ),
+            ],
+            "swirl" => vec![
+                xform(0.0, [0.5, 0.0, 0.0, 0.5, -0.5, 0.0], &[(Swirl, 1.0)]),
+                xform(0.5, [0.5, 0.0, 0.0, 0.5, 0.5, 0.0], &[(Swirl, 1.0)]),
+                xform(1.0, [0.5, 0.0, 0.0, 0.5, 0.0, 0.8], &[(Swirl, 1.0)]),
+            ],
+            "julia" => vec![
+                xform(0.0, [-0.6, -0.5, 0.5, -0.6, 0.3, 0.2], &[(Julia, 1.0)]),
+                xform(1.0, [0.6, -0.2, 0.2, 0.6, -0.4, 0.1], &[(Julia, 1.0)]),
+            ],
+            _ => return None,
+        };
+        Some(Self::new(xforms))
+    }
+
+    /// A built-in flame, or a `.flame` file optionally followed by `:name` to
+    /// pick one of its flames.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if let Some(flame) = Self::builtin(s) {
+            return Ok(flame);
+        }
+        let (path, name) = match s.rsplit_once(':') {
+            Some((path, name)) if is_flame_file(path) => (path, Some(name)),
+            _ => (s, None),
+        };
+        if !is_flame_file(path) {
+            return Err(format!(
+                "'{s}' is neither a built-in flame nor a .flame file"
+            ));
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read '{path}': {err}"))?;
+        parse_flame(&text, name).map_err(|err| format!("{path}: {err}"))
+    }
+
+    /// The transform for `u`, a random number in `[0, 1)`.
+    fn choose(&self, u: f64) -> &Xform {
+        let total: f64 = self.xforms.iter().map(|xform| xform.weight).sum();
+        let mut target = u * total;
+        for xform in &self.xforms {
+            if target < xform.weight {
+                return xform;
+            }
+            target -= xform.weight;
+        }
+        &self.xforms[self.xforms.len() - 1]
+    }
+
+    /// The view of the camera for an image of the given size, keeping the
+    /// width of the view.
+    pub fn view(&self, width: usize, height: usize) -> Option<View> {
+        let camera = self.camera?;
+        let half_width = camera.width / 2.0;
+        let half_height = half_width * height as f64 / width as f64;
+        Some(View {
+            from_x: camera.center.re - half_width,
+            to_x: camera.center.re + half_width,
+            from_y: camera.center.im - half_height,
+            to_y: camera.center.im + half_height,
+        })
+    }
+}
+
+fn is_flame_file(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".flame")
+}
+
+/// Colors and hit counts of every pixel, collected at a multiple of the
+/// image size.
+struct Bins {
+    width: usize,
+    height: usize,
+    view: View,
+    /// Sums of red, green and blue, and the number of hits. Integers keep
+    /// the sums independent of how the work was split between threads.
+    data: Vec<[u32; 4]>,
+}
+
+impl Bins {
+    fn plot(&mut self, p: Complex, color: [u8; 3]) {
+        let View {
+            from_x,
+            to_x,
+            from_y,
+            to_y,
+        } = self.view;
+        let x = map(p.re, from_x, to_x, 0.0, self.width as f64);
+        let y = map(p.im, from_y, to_y, 0.0, self.height as f64);
+        if x >= 0.0 && y >= 0.0 && x < self.width as f64 && y < self.height as f64 {
+            let bin = &mut self.data[y as usize * self.width + x as usize];
+            for (sum, value) in bin.iter_mut().zip(color.into_iter().chain([1])) {
+                *sum = sum.saturating_add(value as u32);
+            }
+        }
+    }
+}
+
+impl Buffer for Bins {
+    fn merge(&mut self, other: &Bins) {
+        for (bin, part) in self.data.iter_mut().zip(&other.data) {
+            for (sum, value) in bin.iter_mut().zip(part) {
+                *sum = sum.saturating_add(*value);
+            }
+        }
+    }
+}
+
+/// Points of the chaos game played from one random start.
+const CHUNK: usize = 1 << 16;
+
+/// Steps taken before a chunk starts plotting.
+const FUSE: usize = 20;
+
+/// Largest number of bins per pixel along each axis; the bins take its
+/// square times the memory of the image.
+pub const MAX_SUPERSAMPLE: usize = 8;
+
+/// Colors looked up from the palette, so the chaos game does not have to
+/// interpolate.
+const LOOKUP: usize = 256;
+
+/// Renders a [`Flame`] with the chaos game. Every hit adds the color of the
+/// point to its pixel; pixels show the average color at a brightness given
+/// by the logarithm of their hit count.
+pub struct FlameRender {
+    pub flame: Flame,
+    pub palette: Palette,
+    pub points: usize,
+    pub seed: u64,
+    /// Applied to the brightness; higher values bring out faint parts.
+    pub gamma: f64,
+    /// How much gamma is applied to the brightness alone (1) rather than to
+    /// every channel (0), which keeps colors saturated.
+    pub vibrancy: f64,
+    /// Bins per pixel along each axis.
+    pub supersample: usize,
+}
+
+impl FlameRender {
+    fn accumulate(&self, settings: &Settings, view: View) -> Bins {
+        let (width, height) = (
+            settings.width * self.supersample,
+            settings.height * self.supersample,
+        );
+        let lookup: Vec<[u8; 3]> = (0..LOOKUP)
+            .map(|i| {
+                let (r, g, b) = self.palette.color(i as f64 / (LOOKUP - 1) as f64);
+                [r, g, b]
+            })
+            .collect();
+        let rotation = match self.flame.camera {
+            Some(camera) if camera.rotate != 0.0 => {
+                Some((camera.center, Complex::from_polar(1.0, -camera.rotate)))
+            }
+            _ => None,
+        };
+
+        let empty = || Bins {
+            width,
+            height,
+            view,
+            data: vec![[0; 4]; width * height],
+        };
+        let chunks = self.points.div_ceil(CHUNK);
+        density::accumulate(settings.threads, chunks, empty, |chunk, bins| {
+            let mut rng = Rng::stream(self.seed, chunk as u64);
+            let mut p = Complex::new(rng.range(-1.0, 1.0), rng.range(-1.0, 1.0));
+            let mut c = rng.next_f64();
+            let count = self.points.min((chunk + 1) * CHUNK) - chunk * CHUNK;
+
+            for i in 0..FUSE + count {
+                let xform = self.flame.choose(rng.next_f64());
+                p = xform.apply(p, &mut rng);
+                c = xform.blend(c);
+                if !(p.re.is_finite() && p.im.is_finite()) {
+                    p = Complex::new(rng.range(-1.0, 1.0), rng.range(-1.0, 1.0));
+                    continue;
+                }
+                if i < FUSE {
+                    continue;
+                }
+
+                let (mut q, shade) = match &self.flame.final_xform {
+                    Some(last) => (last.apply(p, &mut rng), last.blend(c)),
+                    None => (p, c),
+                };
+                if let Some((center, turn)) = rotation {
+                    q = center + (q - center) * turn;
+                }
+                let index = (shade.clamp(0.0, 1.0) * (LOOKUP - 1) as f64).round() as usize;
+                bins.plot(q, lookup[index]);
+            }
+        })
+    }
+
+    /// The color of a bin with the given sums, brighter for more hits.
+    fn tone_map(&self, [r, g, b, hits]: [u32; 4], scale: f64) -> [f64; 3] {
+        if hits == 0 {
+            return [0.0; 3];
+        }
+        let hits = hits as f64;
+        let alpha = (1.0 + hits).ln() * scale;
+        let brightness = alpha.powf(1.0 / self.gamma);
+        [r, g, b].map(|sum| {
+            let color = sum as f64 / hits / 255.0;
+            let vibrant = color * brightness;
+            let plain = (color * alpha).powf(1.0 / self.gamma);
+            self.vibrancy * vibrant + (1.0 - self.vibrancy) * plain
+        })
+    }
+}
+
+impl Draw for FlameRender {
+    fn draw(&self, settings: &Settings) -> Image {
+        let view = self
+            .flame
+            .view(settings.width, settings.height)
+            .unwrap_or(settings.view);
+        let bins = self.accumulate(settings, view);
+
+        // Tone map every bin, then average the bins of each pixel, which
+        // smooths the edges where the brightness changes quickly.
+        let max = bins.data.iter().map(|bin| bin[3]).max().unwrap_or(0).max(1);
+        let scale = 1.0 / (1.0 + max as f64).ln();
+        let ss = self.supersample;
+        let mut pixels = vec![[0.0; 3]; settings.width * settings.height];
+        for (i, &bin) in bins.data.iter().enumerate() {
+            let (x, y) = (i % bins.width / ss, i / bins.width / ss);
+            let pixel = &mut pixels[y * settings.width + x];
+            for (sum, value) in pixel.iter_mut().zip(self.tone_map(bin, scale)) {
+                *sum += value;
+            }
+        }
+
+        let area = (ss * ss) as f64;
+        let mut img = Image::new(settings.width, settings.height);
+        for (out, pixel) in img.data.iter_mut().zip(pixels) {
+            let [r, g, b] = pixel.map(|sum| ((sum / area).clamp(0.0, 1.0) * 255.0).round() as u8);
+            *out = Image::pack(r, g, b);
+        }
+        img
+    }
+}
+
+#[test]
+fn test_variations() {
+    let identity = Affine {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        e: 0.0,
+        f: 0.0,
+    };
+    let mut rng = Rng::new(0);
+    let mut apply = |variation: FlameVariation, p: Complex| variation.apply(p, &identity, &mut rng);
+    let p = Complex::new(0.3, -0.4);
+
+    assert!(apply(FlameVariation::Linear, p) == p);
+    assert!((apply(FlameVariation::Spherical, p) - p / p.norm_sqr()).abs() < 1e-9);
+    assert!(apply(FlameVariation::Sinusoidal, p) == Complex::new(0.3f64.sin(), (-0.4f64).sin()));
+    assert!(apply(FlameVariation::Bent, p) == Complex::new(0.3, -0.2));
+    // Julia takes one of the two square roots.
+    let root = apply(FlameVariation::Julia, p);
+    assert!((root * root - p).abs() < 1e-9);
+    // Swirl keeps distances to the origin.
+    assert!((apply(FlameVariation::Swirl, p).abs() - p.abs()).abs() < 1e-12);
+
+    for variation in FlameVariation::ALL {
+        assert!(FlameVariation::from_name(variation.name()) == Some(variation));
+        let q = apply(variation, p);
+        assert!(
+            q.re.is_finite() && q.im.is_finite(),
+            "{variation:?} is not finite."
+        );
+        let q = apply(variation, Complex::ZERO);
+        assert!(!q.re.is_nan(), "{variation:?} is undefined at the origin.");
+    }
+}
+
+#[test]
+fn test_xform() {
+    let xform = Xform {
+        weight: 1.0,
+        affine: Affine {
+            a: 2.0,
+            b: 0.0,
+            c: 0.0,
+            d: 2.0,
+            e: 1.0,
+            f: 0.0,
+        },
+        variations: vec![
+            (FlameVariation::Linear, 0.25),
+            (FlameVariation::Spherical, 0.5),
+        ],
+        post: Some(Affine {
+            a: 1.0,
+            b: 0.0,
+            c: 0.0,
+            d: 1.0,
+            e: 0.0,
+            f: 1.0,
+        }),
+        color: 1.0,
+        color_speed: 0.25,
+    };
+    let q = xform.apply(Complex::new(0.5, 0.0), &mut Rng::new(0));
+    assert!((q - Complex::new(0.5 + 0.25, 1.0)).abs() < 1e-9);
+    assert!(xform.blend(0.0) == 0.25);
+}
+
+#[test]
+fn test_flame_render() {
+    let settings = density::test_settings(1);
+    let render = |threads, supersample| {
+        let flame = FlameRender {
+            flame: Flame::builtin("spherical").unwrap(),
+            palette: Palette::builtin("fire").unwrap(),
+            points: CHUNK + 100,
+            seed: 5,
+            gamma: 2.2,
+            vibrancy: 1.0,
+            supersample,
+        };
+        flame.draw(&Settings {
+            threads,
+            ..settings
+        })
+    };
+    let one = render(1, 2);
+    assert!(
+        one.data == render(3, 2).data,
+        "The threads changed the result."
+    );
+    assert!(one.data.iter().any(|&pixel| pixel != 0));
+    assert!(one.data != render(1, 1).data);
+}
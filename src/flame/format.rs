@@ -0,0 +1,445 @@
+//! Flames saved by Apophysis, Chaotica and flam3 (`.flame`): XML with one
+//! `<flame>` element per flame, holding its `<xform>`s, an optional
+//! `<finalxform>` and the palette as `<color>` elements or a `<palette>` of
+//! hex digits.
+
+use super::{Camera, Flame, FlameVariation, Xform, MAX_SUPERSAMPLE};
+use crate::ifs::Affine;
+use crate::palette::{Palette, Stop};
+use crate::Complex;
+
+/// Attributes of `<xform>` that do not change how it is drawn here.
+const IGNORED: [&str; 8] = [
+    "name",
+    "opacity",
+    "animate",
+    "chaos",
+    "var_color",
+    "plotmode",
+    "visible",
+    "motion_frequency",
+];
+
+/// One element of the file, with the text up to the next one.
+#[derive(Debug)]
+struct Tag<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, String)>,
+    closing: bool,
+    text: &'a str,
+}
+
+impl Tag<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Splits `text` into its elements, skipping comments, declarations and
+/// processing instructions.
+fn tags(text: &str) -> Result<Vec<Tag<'_>>, String> {
+    let mut tags = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            let end = comment.find("-->").ok_or("a comment is not closed")?;
+            rest = &comment[end + 3..];
+            continue;
+        }
+        let end = tag_end(rest).ok_or("a tag is not closed")?;
+        let inside = &rest[..end];
+        rest = &rest[end + 1..];
+        if inside.starts_with('?') || inside.starts_with('!') {
+            continue;
+        }
+        let text = &rest[..rest.find('<').unwrap_or(rest.len())];
+
+        let (closing, inside) = match inside.strip_prefix('/') {
+            Some(inside) => (true, inside),
+            None => (false, inside),
+        };
+        let (empty, inside) = match inside.strip_suffix('/') {
+            Some(inside) => (true, inside),
+            None => (false, inside),
+        };
+        let inside = inside.trim();
+        let name_end = inside
+            .find(|c: char| c.is_whitespace())
+            .unwrap_or(inside.len());
+        let name = &inside[..name_end];
+        let attributes =
+            attributes(&inside[name_end..]).map_err(|err| format!("<{name}>: {err}"))?;
+        tags.push(Tag {
+            name,
+            attributes,
+            closing,
+            text,
+        });
+        if empty {
+            tags.push(Tag {
+                name,
+                attributes: Vec::new(),
+                closing: true,
+                text,
+            });
+        }
+    }
+    Ok(tags)
+}
+
+/// The position of the `>` ending a tag, skipping quoted values.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Attributes `name="value"`, with single or double quotes.
+fn attributes(mut s: &str) -> Result<Vec<(&str, String)>, String> {
+    let mut attributes = Vec::new();
+    loop {
+        s = s.trim_start();
+        if s.is_empty() {
+            return Ok(attributes);
+        }
+        let (name, rest) = s.split_once('=').ok_or("an attribute has no value")?;
+        let rest = rest.trim_start();
+        let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'');
+        let quote = quote.ok_or_else(|| format!("the value of '{}' is not quoted", name.trim()))?;
+        let end = rest[1..]
+            .find(quote)
+            .ok_or_else(|| format!("the value of '{}' is not closed", name.trim()))?;
+        attributes.push((name.trim(), unescape(&rest[1..end + 1])));
+        s = &rest[end + 2..];
+    }
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// The numbers of an attribute, separated by white space.
+fn numbers(name: &str, value: &str) -> Result<Vec<f64>, String> {
+    value
+        .split_whitespace()
+        .map(|token| {
+            token
+                .parse::<f64>()
+                .map_err(|_| format!("'{name}' has '{token}', which is not a number"))
+        })
+        .collect()
+}
+
+fn number(name: &str, value: &str) -> Result<f64, String> {
+    match numbers(name, value)?[..] {
+        [first, ..] => Ok(first),
+        [] => Err(format!("'{name}' is empty")),
+    }
+}
+
+/// Coefficients `xx xy yx yy ox oy`, where `x' = xx x + yx y + ox` and
+/// `y' = xy x + yy y + oy`.
+fn affine(name: &str, value: &str) -> Result<Affine, String> {
+    match numbers(name, value)?[..] {
+        [a, c, b, d, e, f] => Ok(Affine { a, b, c, d, e, f }),
+        _ => Err(format!("'{name}' needs six numbers")),
+    }
+}
+
+fn xform(tag: &Tag) -> Result<Xform, String> {
+    let mut xform = Xform {
+        weight: 1.0,
+        affine: Affine {
+            a: 1.0,
+            b: 0.0,
+            c: 0.0,
+            d: 1.0,
+            e: 0.0,
+            f: 0.0,
+        },
+        variations: Vec::new(),
+        post: None,
+        color: 0.0,
+        color_speed: 0.5,
+    };
+    for (name, value) in &tag.attributes {
+        match *name {
+            "weight" => xform.weight = number(name, value)?,
+            "color" => xform.color = number(name, value)?,
+            // Apophysis writes the symmetry, flam3 2 and Chaotica the speed.
+            "symmetry" => xform.color_speed = (1.0 - number(name, value)?) / 2.0,
+            "color_speed" => xform.color_speed = number(name, value)?,
+            "coefs" => xform.affine = affine(name, value)?,
+            "post" => xform.post = Some(affine(name, value)?),
+            name if IGNORED.contains(&name) => {}
+            name => {
+                let weight = number(name, value)?;
+                match FlameVariation::from_name(name) {
+                    Some(variation) => xform.variations.push((variation, weight)),
+                    // Unknown variations and their parameters only matter
+                    // when they are used.
+                    None if weight == 0.0 => {}
+                    None => return Err(format!("the variation '{name}' is not supported")),
+                }
+            }
+        }
+    }
+    if !(xform.weight >= 0.0 && xform.weight.is_finite()) {
+        return Err("transform weights must not be negative".to_string());
+    }
+    Ok(xform)
+}
+
+/// The colors of a `<palette>` element: six hex digits per color, or eight
+/// with a leading alpha.
+fn hex_palette(tag: &Tag) -> Result<Vec<[u8; 3]>, String> {
+    let digits: Vec<u8> = tag
+        .text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    let count = match tag.get("count") {
+        Some(count) => count
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| "the palette count is not a number".to_string())?,
+        None => digits.len() / 6,
+    };
+    if count == 0 {
+        return Err("the palette is empty".to_string());
+    }
+    let width = digits.len() / count;
+    if (width != 6 && width != 8) || !digits.len().is_multiple_of(count) {
+        return Err(format!("the palette does not hold {count} colors"));
+    }
+    digits
+        .chunks(width)
+        .map(|color| {
+            let color = std::str::from_utf8(&color[width - 6..]).unwrap();
+            let value = u32::from_str_radix(color, 16)
+                .map_err(|_| format!("'{color}' is not a hex color"))?;
+            Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+        })
+        .collect()
+}
+
+/// The size, center, scale, zoom and rotation of a flame as its view.
+fn camera(tag: &Tag) -> Result<Option<Camera>, String> {
+    let (Some(size), Some(scale)) = (tag.get("size"), tag.get("scale")) else {
+        return Ok(None);
+    };
+    let width = number("size", size)?;
+    let scale = number("scale", scale)?;
+    let zoom = tag
+        .get("zoom")
+        .map_or(Ok(0.0), |zoom| number("zoom", zoom))?;
+    let center = match tag.get("center") {
+        Some(center) => match numbers("center", center)?[..] {
+            [x, y] => Complex::new(x, y),
+            _ => return Err("'center' needs two numbers".to_string()),
+        },
+        None => Complex::ZERO,
+    };
+    let rotate = tag
+        .get("rotate")
+        .map_or(Ok(0.0), |rotate| number("rotate", rotate))?;
+    if !(width > 0.0 && scale > 0.0) {
+        return Err("the size and scale must be positive".to_string());
+    }
+    Ok(Some(Camera {
+        center,
+        width: width / (scale * zoom.exp2()),
+        rotate: rotate.to_radians(),
+    }))
+}
+
+fn flame(tag: &Tag) -> Result<Flame, String> {
+    let setting = |name| tag.get(name).map(|value| number(name, value)).transpose();
+    let supersample = match setting("supersample")? {
+        Some(n) if (1.0..=MAX_SUPERSAMPLE as f64).contains(&n) => Some(n as usize),
+        Some(_) => {
+            return Err(format!(
+                "the supersampling must be between 1 and {MAX_SUPERSAMPLE}"
+            ))
+        }
+        None => None,
+    };
+    let gamma = match setting("gamma")? {
+        Some(gamma) if gamma > 0.0 && gamma.is_finite() => Some(gamma),
+        Some(_) => return Err("the gamma must be greater than zero".to_string()),
+        None => None,
+    };
+    let vibrancy = match setting("vibrancy")? {
+        Some(vibrancy) if (0.0..=1.0).contains(&vibrancy) => Some(vibrancy),
+        Some(_) => return Err("the vibrancy must be between 0 and 1".to_string()),
+        None => None,
+    };
+    Ok(Flame {
+        xforms: Vec::new(),
+        final_xform: None,
+        palette: None,
+        camera: camera(tag)?,
+        gamma,
+        vibrancy,
+        supersample,
+    })
+}
+
+/// A `.flame` file. Picks the flame called `name`, or the first one.
+pub fn parse_flame(text: &str, name: Option<&str>) -> Result<Flame, String> {
+    let tags = tags(text)?;
+    let mut tags = tags.iter();
+    while let Some(tag) = tags.next() {
+        if tag.name != "flame" || tag.closing {
+            continue;
+        }
+        let title = tag.get("name").unwrap_or("");
+        if name.is_some_and(|name| name != title) {
+            continue;
+        }
+        return parse_body(tag, &mut tags).map_err(|err| match title {
+            "" => err,
+            title => format!("{title}: {err}"),
+        });
+    }
+    match name {
+        Some(name) => Err(format!("there is no flame called '{name}'")),
+        None => Err("there is no flame".to_string()),
+    }
+}
+
+/// The elements of a flame up to its closing tag.
+fn parse_body<'a>(
+    open: &Tag,
+    tags: &mut impl Iterator<Item = &'a Tag<'a>>,
+) -> Result<Flame, String> {
+    let mut flame = flame(open)?;
+    let mut colors = Vec::new();
+    for tag in tags {
+        if tag.closing {
+            if tag.name == "flame" {
+                break;
+            }
+            continue;
+        }
+        match tag.name {
+            "xform" => flame.xforms.push(xform(tag)?),
+            "finalxform" => flame.final_xform = Some(xform(tag)?),
+            "color" => {
+                let index = tag.get("index").ok_or("a color has no index")?;
+                let index = number("index", index)?;
+                if !(0.0..=255.0).contains(&index) {
+                    return Err(format!("the color index {index} is not between 0 and 255"));
+                }
+                let rgb = tag.get("rgb").ok_or("a color has no rgb")?;
+                let [r, g, b] = numbers("rgb", rgb)?[..] else {
+                    return Err("'rgb' needs three numbers".to_string());
+                };
+                let channel = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+                colors.push(Stop {
+                    position: index / 255.0,
+                    color: [channel(r), channel(g), channel(b)],
+                });
+            }
+            "palette" => {
+                flame.palette = Some(Palette::even(&hex_palette(tag)?));
+            }
+            _ => {}
+        }
+    }
+    // The colors sit at their own indices out of 256, in any order.
+    if !colors.is_empty() {
+        flame.palette = Some(Palette::new(colors));
+    }
+    if flame.xforms.is_empty() {
+        return Err("the flame has no transforms".to_string());
+    }
+    if flame.xforms.iter().all(|xform| xform.weight == 0.0) {
+        return Err("the transform weights add up to zero".to_string());
+    }
+    Ok(flame)
+}
+
+#[test]
+fn test_tags() {
+    let parsed =
+        tags("<?xml version=\"1.0\"?><!-- <a> --><a x='1 2' y=\"&lt;\"/> text <b>ff</b>").unwrap();
+    let names: Vec<(&str, bool)> = parsed.iter().map(|tag| (tag.name, tag.closing)).collect();
+    assert!(names == [("a", false), ("a", true), ("b", false), ("b", true)]);
+    assert!(parsed[0].get("x") == Some("1 2") && parsed[0].get("y") == Some("<"));
+    assert!(parsed[2].text == "ff");
+    assert!(tags("<a x=\"1>").is_err(), "An open tag was accepted.");
+}
+
+#[test]
+fn test_flame_file() {
+    let text = include_str!("../../flames/sample.flame");
+    let first = parse_flame(text, None).unwrap();
+    assert!(first.xforms.len() == 2 && first.final_xform.is_none());
+    let xform = &first.xforms[1];
+    assert!(xform.weight == 0.5 && xform.color == 1.0 && xform.color_speed == 0.25);
+    assert!(xform.affine.b == 0.25 && xform.affine.c == -0.25 && xform.affine.e == 0.5);
+    assert!(
+        xform.variations
+            == [
+                (FlameVariation::Julia, 0.75),
+                (FlameVariation::Spherical, 0.25)
+            ]
+    );
+    assert!(first.gamma == Some(3.0) && first.supersample == Some(2));
+    let camera = first.camera.unwrap();
+    assert!(camera.width == 4.0 && camera.center == Complex::new(0.5, 0.0));
+    assert!(first.palette.unwrap().color(0.0) == (255, 0, 0));
+
+    let second = parse_flame(text, Some("hex")).unwrap();
+    assert!(second.final_xform.unwrap().post.is_some());
+    assert!(second.palette.unwrap().color(1.0) == (0, 0, 255));
+    assert!(second.camera.is_none());
+
+    assert!(
+        parse_flame(text, Some("blur")).is_err(),
+        "An unsupported variation was accepted."
+    );
+    assert!(parse_flame(text, Some("missing")).is_err());
+
+    // Settings are checked like the command line options.
+    let xform = "<xform weight=\"1\" coefs=\"1 0 0 1 0 0\" linear=\"1\"/>";
+    let with = |setting: &str| parse_flame(&format!("<flame {setting}>{xform}</flame>"), None);
+    assert!(with("gamma=\"2.5\" vibrancy=\"0\" supersample=\"3\"").is_ok());
+    for setting in [
+        "gamma=\"0\"",
+        "gamma=\"-1\"",
+        "vibrancy=\"1.5\"",
+        "supersample=\"1000\"",
+    ] {
+        assert!(with(setting).is_err(), "{setting} was accepted.");
+    }
+
+    // Palette colors sit at their indices, whatever their order.
+    let colors = "<color index=\"255\" rgb=\"0 0 255\"/><color index=\"0\" rgb=\"255 0 0\"/>\
+                  <color index=\"51\" rgb=\"0 255 0\"/>";
+    let sparse = parse_flame(&format!("<flame>{xform}{colors}</flame>"), None).unwrap();
+    let palette = sparse.palette.unwrap();
+    assert!(palette.color(0.0) == (255, 0, 0) && palette.color(1.0) == (0, 0, 255));
+    assert!(
+        palette.color(51.0 / 255.0) == (0, 255, 0),
+        "The colors were spaced evenly."
+    );
+    let outside = "<color index=\"256\" rgb=\"0 0 0\"/>";
+    assert!(parse_flame(&format!("<flame>{xform}{outside}</flame>"), None).is_err());
+}
@@ -0,0 +1,807 @@
+//! A small expression language for user-defined escape-time fractals, e.g.
+//! `z^3 + c`, `sin(z) * c` or `z^2 + c/z`.
+//!
+//! Source text is parsed into an AST, type-checked (numbers are complex,
+//! comparisons and `&&`, `||`, `!` produce booleans) and compiled to
+//! bytecode for a small stack machine over [`Complex`].
+
+use std::f64::consts::{E, PI};
+use std::fmt::{self, Display};
+
+use crate::fractal::{Escape, Fractal, Orbit};
+use crate::Complex;
+
+/// Variables every formula can use, in slot order. User parameters follow.
+const VARIABLES: [&str; 4] = ["z", "c", "pixel", "bailout"];
+
+const Z: usize = 0;
+const C: usize = 1;
+const PIXEL: usize = 2;
+const BAILOUT: usize = 3;
+
+/// Names that always stand for a number, ahead of any variable.
+const CONSTANTS: [&str; 3] = ["i", "pi", "e"];
+
+#[derive(Debug, PartialEq)]
+pub struct FormulaError {
+    /// Column of the offending token, counted from 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+fn error<T>(column: usize, message: impl Into<String>) -> Result<T, FormulaError> {
+    Err(FormulaError {
+        column,
+        message: message.into(),
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Imaginary(f64),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+/// Longer symbols come first so that `<=` is not read as `<` and `=`.
+const SYMBOLS: [&str; 17] = [
+    "||", "&&", "<=", ">=", "==", "!=", "+", "-", "*", "/", "^", "(", ")", "|", "<", ">", "!",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, FormulaError> {
+    let mut tokens = Vec::new();
+    let chars = source.char_indices().collect::<Vec<_>>();
+    let mut i = 0;
+
+    while let Some(&(start, ch)) = chars.get(i) {
+        let column = source[..start].chars().count() + 1;
+        if ch.is_whitespace() {
+            i += 1;
+        } else if ch.is_ascii_digit() || ch == '.' {
+            let mut end = i;
+            while let Some(&(j, c)) = chars.get(end) {
+                let exponent_sign =
+                    (c == '+' || c == '-') && matches!(source[..j].chars().last(), Some('e' | 'E'));
+                if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign {
+                    end += 1;
+                } else {
+                    break;
+                }
+            }
+            let stop = chars.get(end).map_or(source.len(), |&(j, _)| j);
+            let Ok(value) = source[start..stop].parse::<f64>() else {
+                return error(
+                    column,
+                    format!("'{}' is not a number", &source[start..stop]),
+                );
+            };
+            // `2i` is an imaginary literal, but `2in` would be a typo.
+            let imaginary = chars.get(end).is_some_and(|&(_, c)| c == 'i')
+                && !chars
+                    .get(end + 1)
+                    .is_some_and(|&(_, c)| c.is_alphanumeric() || c == '_');
+            if imaginary {
+                tokens.push((Token::Imaginary(value), column));
+                end += 1;
+            } else {
+                tokens.push((Token::Number(value), column));
+            }
+            i = end;
+        } else if ch.is_alphabetic() || ch == '_' {
+            let mut end = i;
+            while chars
+                .get(end)
+                .is_some_and(|&(_, c)| c.is_alphanumeric() || c == '_')
+            {
+                end += 1;
+            }
+            let stop = chars.get(end).map_or(source.len(), |&(j, _)| j);
+            tokens.push((Token::Ident(source[start..stop].to_string()), column));
+            i = end;
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| source[start..].starts_with(**s)) {
+            tokens.push((Token::Symbol(symbol), column));
+            i += symbol.len();
+        } else {
+            return error(column, format!("unexpected character '{ch}'"));
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Ln,
+    Sqrt,
+    Abs,
+    Norm,
+    Arg,
+    Re,
+    Im,
+    Conj,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sin" => Self::Sin,
+            "cos" => Self::Cos,
+            "tan" => Self::Tan,
+            "sinh" => Self::Sinh,
+            "cosh" => Self::Cosh,
+            "tanh" => Self::Tanh,
+            "exp" => Self::Exp,
+            "ln" | "log" => Self::Ln,
+            "sqrt" => Self::Sqrt,
+            "abs" => Self::Abs,
+            "norm" => Self::Norm,
+            "arg" => Self::Arg,
+            "re" | "real" => Self::Re,
+            "im" | "imag" => Self::Im,
+            "conj" => Self::Conj,
+            _ => return None,
+        })
+    }
+
+    fn apply(self, z: Complex) -> Complex {
+        let real = |v: f64| Complex::new(v, 0.0);
+        match self {
+            Self::Sin => z.sin(),
+            Self::Cos => z.cos(),
+            Self::Tan => z.tan(),
+            Self::Sinh => z.sinh(),
+            Self::Cosh => z.cosh(),
+            Self::Tanh => z.tanh(),
+            Self::Exp => z.exp(),
+            Self::Ln => z.ln(),
+            Self::Sqrt => z.sqrt(),
+            Self::Abs => real(z.abs()),
+            Self::Norm => real(z.norm_sqr()),
+            Self::Arg => real(z.arg()),
+            Self::Re => real(z.re),
+            Self::Im => real(z.im),
+            Self::Conj => z.conj(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Binary {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Constant(Complex),
+    Variable(usize),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Call(Function, Box<Expr>),
+    Binary(Binary, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Type {
+    Number,
+    Bool,
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Type::Number => "a number",
+            Type::Bool => "a condition",
+        })
+    }
+}
+
+/// Recursive-descent parser. Precedence from loosest to tightest: `||`,
+/// `&&`, comparisons, `+ -`, `* /`, unary `- !`, and right-associative `^`.
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    variables: &'a [String],
+    end: usize,
+}
+
+/// A parsed expression together with the column it started at, so type
+/// errors can point at it.
+type Spanned = (Expr, usize);
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.position).map_or(self.end, |&(_, c)| c)
+    }
+
+    fn eat(&mut self, symbol: &'static str) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), FormulaError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            error(self.column(), format!("expected '{symbol}'"))
+        }
+    }
+
+    fn binary_level(
+        &mut self,
+        operators: &[(&'static str, Binary)],
+        next: fn(&mut Self) -> Result<Spanned, FormulaError>,
+    ) -> Result<Spanned, FormulaError> {
+        let (mut lhs, column) = next(self)?;
+        'outer: loop {
+            for &(symbol, op) in operators {
+                if self.eat(symbol) {
+                    let (rhs, _) = next(self)?;
+                    lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok((lhs, column));
+        }
+    }
+
+    fn or(&mut self) -> Result<Spanned, FormulaError> {
+        self.binary_level(&[("||", Binary::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Spanned, FormulaError> {
+        self.binary_level(&[("&&", Binary::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Spanned, FormulaError> {
+        self.binary_level(
+            &[
+                ("<=", Binary::LessEqual),
+                (">=", Binary::GreaterEqual),
+                ("<", Binary::Less),
+                (">", Binary::Greater),
+                ("==", Binary::Equal),
+                ("!=", Binary::NotEqual),
+            ],
+            Self::sum,
+        )
+    }
+
+    fn sum(&mut self) -> Result<Spanned, FormulaError> {
+        self.binary_level(&[("+", Binary::Add), ("-", Binary::Sub)], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Spanned, FormulaError> {
+        self.binary_level(&[("*", Binary::Mul), ("/", Binary::Div)], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Spanned, FormulaError> {
+        let column = self.column();
+        if self.eat("-") {
+            let (operand, _) = self.unary()?;
+            return Ok((Expr::Neg(Box::new(operand)), column));
+        }
+        if self.eat("!") {
+            let (operand, _) = self.unary()?;
+            return Ok((Expr::Not(Box::new(operand)), column));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Spanned, FormulaError> {
+        let (base, column) = self.atom()?;
+        if self.eat("^") {
+            let (exponent, _) = self.unary()?;
+            return Ok((
+                Expr::Binary(Binary::Pow, Box::new(base), Box::new(exponent)),
+                column,
+            ));
+        }
+        Ok((base, column))
+    }
+
+    fn atom(&mut self) -> Result<Spanned, FormulaError> {
+        let column = self.column();
+        let Some(token) = self.peek().cloned() else {
+            return error(column, "unexpected end of formula");
+        };
+        self.position += 1;
+
+        let expr = match token {
+            Token::Number(value) => Expr::Constant(Complex::new(value, 0.0)),
+            Token::Imaginary(value) => Expr::Constant(Complex::new(0.0, value)),
+            Token::Symbol("(") => {
+                let (expr, _) = self.or()?;
+                self.expect(")")?;
+                expr
+            }
+            Token::Symbol("|") => {
+                let (expr, _) = self.sum()?;
+                self.expect("|")?;
+                Expr::Call(Function::Abs, Box::new(expr))
+            }
+            Token::Ident(name) if self.eat("(") => {
+                let Some(function) = Function::from_name(&name) else {
+                    return error(column, format!("unknown function '{name}'"));
+                };
+                let (argument, _) = self.or()?;
+                self.expect(")")?;
+                Expr::Call(function, Box::new(argument))
+            }
+            Token::Ident(name) => match name.as_str() {
+                "i" => Expr::Constant(Complex::I),
+                "pi" => Expr::Constant(Complex::new(PI, 0.0)),
+                "e" => Expr::Constant(Complex::new(E, 0.0)),
+                _ => match self.variables.iter().position(|v| *v == name) {
+                    Some(slot) => Expr::Variable(slot),
+                    None => return error(column, format!("unknown variable '{name}'")),
+                },
+            },
+            Token::Symbol(symbol) => return error(column, format!("unexpected '{symbol}'")),
+        };
+        Ok((expr, column))
+    }
+}
+
+fn parse(source: &str, variables: &[String]) -> Result<Spanned, FormulaError> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        position: 0,
+        variables,
+        end: source.chars().count() + 1,
+    };
+    let expr = parser.or()?;
+    if parser.position < parser.tokens.len() {
+        return error(parser.column(), "unexpected text after the formula");
+    }
+    Ok(expr)
+}
+
+/// Checks operand types and returns the type of `expr`. Comparisons look at
+/// real parts only, except for `==` and `!=`.
+fn check(expr: &Expr, column: usize) -> Result<Type, FormulaError> {
+    let expect = |expr: &Expr, expected: Type| -> Result<(), FormulaError> {
+        let found = check(expr, column)?;
+        if found == expected {
+            Ok(())
+        } else {
+            error(column, format!("expected {expected} but found {found}"))
+        }
+    };
+
+    match expr {
+        Expr::Constant(_) | Expr::Variable(_) => Ok(Type::Number),
+        Expr::Neg(operand) | Expr::Call(_, operand) => {
+            expect(operand, Type::Number)?;
+            Ok(Type::Number)
+        }
+        Expr::Not(operand) => {
+            expect(operand, Type::Bool)?;
+            Ok(Type::Bool)
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            let (operands, result) = match op {
+                Binary::Add | Binary::Sub | Binary::Mul | Binary::Div | Binary::Pow => {
+                    (Type::Number, Type::Number)
+                }
+                Binary::And | Binary::Or => (Type::Bool, Type::Bool),
+                _ => (Type::Number, Type::Bool),
+            };
+            expect(lhs, operands)?;
+            expect(rhs, operands)?;
+            Ok(result)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Op {
+    Push(Complex),
+    Load(usize),
+    Neg,
+    Not,
+    Call(Function),
+    Binary(Binary),
+    /// `^` with a small integer exponent known at compile time.
+    PowInt(i32),
+}
+
+/// Bytecode for one expression. Booleans are represented as 1 and 0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    ops: Vec<Op>,
+}
+
+impl Program {
+    /// Parses, type-checks and compiles `source`, which must evaluate to
+    /// `expected`.
+    fn compile(source: &str, variables: &[String], expected: Type) -> Result<Self, FormulaError> {
+        let (expr, column) = parse(source, variables)?;
+        let found = check(&expr, column)?;
+        if found != expected {
+            return error(column, format!("expected {expected} but found {found}"));
+        }
+
+        let mut ops = Vec::new();
+        emit(&expr, &mut ops);
+        Ok(Self { ops })
+    }
+
+    fn eval(&self, variables: &[Complex], stack: &mut Vec<Complex>) -> Complex {
+        let truth = |b: bool| if b { Complex::ONE } else { Complex::ZERO };
+        stack.clear();
+
+        for op in &self.ops {
+            let value = match *op {
+                Op::Push(value) => value,
+                Op::Load(slot) => variables[slot],
+                Op::Neg => -stack.pop().unwrap(),
+                Op::Not => truth(stack.pop().unwrap() == Complex::ZERO),
+                Op::Call(function) => function.apply(stack.pop().unwrap()),
+                Op::PowInt(n) => stack.pop().unwrap().powi(n),
+                Op::Binary(op) => {
+                    let rhs = stack.pop().unwrap();
+                    let lhs = stack.pop().unwrap();
+                    match op {
+                        Binary::Add => lhs + rhs,
+                        Binary::Sub => lhs - rhs,
+                        Binary::Mul => lhs * rhs,
+                        Binary::Div => lhs / rhs,
+                        Binary::Pow if rhs.im == 0.0 => lhs.powf(rhs.re),
+                        Binary::Pow => lhs.powc(rhs),
+                        Binary::Less => truth(lhs.re < rhs.re),
+                        Binary::Greater => truth(lhs.re > rhs.re),
+                        Binary::LessEqual => truth(lhs.re <= rhs.re),
+                        Binary::GreaterEqual => truth(lhs.re >= rhs.re),
+                        Binary::Equal => truth(lhs == rhs),
+                        Binary::NotEqual => truth(lhs != rhs),
+                        Binary::And => truth(lhs != Complex::ZERO && rhs != Complex::ZERO),
+                        Binary::Or => truth(lhs != Complex::ZERO || rhs != Complex::ZERO),
+                    }
+                }
+            };
+            stack.push(value);
+        }
+
+        stack.pop().unwrap()
+    }
+}
+
+fn emit(expr: &Expr, ops: &mut Vec<Op>) {
+    match expr {
+        Expr::Constant(value) => ops.push(Op::Push(*value)),
+        Expr::Variable(slot) => ops.push(Op::Load(*slot)),
+        Expr::Neg(operand) => {
+            emit(operand, ops);
+            ops.push(Op::Neg);
+        }
+        Expr::Not(operand) => {
+            emit(operand, ops);
+            ops.push(Op::Not);
+        }
+        Expr::Call(function, operand) => {
+            emit(operand, ops);
+            ops.push(Op::Call(*function));
+        }
+        Expr::Binary(Binary::Pow, base, exponent) => {
+            emit(base, ops);
+            match **exponent {
+                Expr::Constant(n) if n.im == 0.0 && n.re.fract() == 0.0 && n.re.abs() <= 64.0 => {
+                    ops.push(Op::PowInt(n.re as i32))
+                }
+                _ => {
+                    emit(exponent, ops);
+                    ops.push(Op::Binary(Binary::Pow));
+                }
+            }
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            emit(lhs, ops);
+            emit(rhs, ops);
+            ops.push(Op::Binary(*op));
+        }
+    }
+}
+
+/// Source text of a user-defined fractal.
+pub struct FormulaSource<'a> {
+    /// The next `z`, e.g. `z^2 + c`.
+    pub step: &'a str,
+    /// The starting `z`.
+    pub init: &'a str,
+    /// The constant `c`; may use `pixel` but not `z` or `c`, which it comes
+    /// before.
+    pub c: &'a str,
+    /// The condition under which the orbit has escaped, e.g. `|z| > 2`.
+    pub bailout: &'a str,
+}
+
+impl Default for FormulaSource<'_> {
+    fn default() -> Self {
+        Self {
+            step: "z^2 + c",
+            init: "0",
+            c: "pixel",
+            bailout: "|z| > bailout",
+        }
+    }
+}
+
+/// A compiled user-defined escape-time fractal.
+pub struct Formula {
+    init: Program,
+    c: Program,
+    step: Program,
+    bailout: Program,
+    params: Vec<Complex>,
+}
+
+impl Formula {
+    /// Compiles the formulas, where `params` are extra named constants.
+    pub fn new(source: &FormulaSource, params: &[(String, Complex)]) -> Result<Self, String> {
+        let mut variables = VARIABLES.map(String::from).to_vec();
+        for (name, _) in params {
+            if CONSTANTS.contains(&name.as_str()) {
+                return Err(format!(
+                    "'{name}' is a built-in constant and cannot be a parameter name"
+                ));
+            }
+            if variables.contains(name) || Function::from_name(name).is_some() {
+                return Err(format!("the parameter name '{name}' is already taken"));
+            }
+            variables.push(name.clone());
+        }
+
+        // The escape radius belongs to the escape test alone; orbits iterated
+        // through `next` do not know it.
+        let mut without_bailout = variables.clone();
+        without_bailout[BAILOUT].clear();
+        // The constant is computed first, before there is a `z` or `c`.
+        let mut constant = without_bailout.clone();
+        constant[Z].clear();
+        constant[C].clear();
+
+        let compile = |what: &str, source: &str, variables: &[String], expected: Type| {
+            Program::compile(source, variables, expected).map_err(|mut err| {
+                if err.message == "unknown variable 'bailout'" {
+                    err.message = "only the bailout formula may use 'bailout'".to_string();
+                } else if let Some(name) = [VARIABLES[Z], VARIABLES[C]]
+                    .into_iter()
+                    .find(|name| err.message == format!("unknown variable '{name}'"))
+                {
+                    err.message = format!("the constant cannot use '{name}'");
+                }
+                format!("in the {what} formula '{source}', {err}")
+            })
+        };
+        Ok(Self {
+            init: compile("initial", source.init, &without_bailout, Type::Number)?,
+            c: compile("constant", source.c, &constant, Type::Number)?,
+            step: compile("step", source.step, &without_bailout, Type::Number)?,
+            bailout: compile("bailout", source.bailout, &variables, Type::Bool)?,
+            params: params.iter().map(|&(_, value)| value).collect(),
+        })
+    }
+
+    fn variables(&self, pixel: Complex, bailout: f64) -> Vec<Complex> {
+        let mut variables = vec![Complex::ZERO; VARIABLES.len()];
+        variables[PIXEL] = pixel;
+        variables[BAILOUT] = Complex::new(bailout, 0.0);
+        variables.extend(&self.params);
+        variables
+    }
+}
+
+impl Fractal for Formula {
+    fn start(&self, pixel: Complex) -> (Complex, Complex) {
+        let mut variables = self.variables(pixel, 0.0);
+        let mut stack = Vec::new();
+        variables[C] = self.c.eval(&variables, &mut stack);
+        let z = self.init.eval(&variables, &mut stack);
+        (z, variables[C])
+    }
+
+    fn next(&self, orbit: &Orbit) -> Complex {
+        let mut variables = self.variables(orbit.pixel, 0.0);
+        variables[Z] = orbit.z;
+        variables[C] = orbit.c;
+        self.step.eval(&variables, &mut Vec::new())
+    }
+
+    fn escape(&self, pixel: Complex, bailout_sqr: f64, max_iterations: usize) -> Escape {
+        self.escape_with(pixel, bailout_sqr, max_iterations, &mut |_| {})
+    }
+
+    fn escape_with(
+        &self,
+        pixel: Complex,
+        bailout_sqr: f64,
+        max_iterations: usize,
+        visit: &mut dyn FnMut(&Orbit),
+    ) -> Escape {
+        let mut variables = self.variables(pixel, bailout_sqr.sqrt());
+        let mut stack = Vec::new();
+        variables[C] = self.c.eval(&variables, &mut stack);
+        variables[Z] = self.init.eval(&variables, &mut stack);
+
+        let mut iterations = 0;
+        while iterations < max_iterations
+            && self.bailout.eval(&variables, &mut stack) == Complex::ZERO
+        {
+            let previous = variables[Z];
+            variables[Z] = self.step.eval(&variables, &mut stack);
+            iterations += 1;
+            visit(&Orbit {
+                z: variables[Z],
+                previous,
+                c: variables[C],
+                pixel,
+            });
+        }
+
+        Escape {
+            iterations,
+            z: variables[Z],
+        }
+    }
+}
+
+#[cfg(test)]
+fn evaluate(source: &str, z: Complex) -> Result<Complex, FormulaError> {
+    let variables = VARIABLES.map(String::from);
+    let program = Program::compile(source, &variables, Type::Number)?;
+    let values = [
+        z,
+        Complex::new(0.5, -0.25),
+        Complex::ZERO,
+        Complex::new(2.0, 0.0),
+    ];
+    Ok(program.eval(&values, &mut Vec::new()))
+}
+
+#[test]
+fn test_formula_eval() {
+    let z = Complex::new(0.3, 0.7);
+    let c = Complex::new(0.5, -0.25);
+
+    assert!(evaluate("z^2 + c", z) == Ok(z * z + c));
+    assert!(evaluate("z^3 + c", z) == Ok(z.powi(3) + c));
+    assert!(evaluate("sin(z)*c", z) == Ok(z.sin() * c));
+    assert!(evaluate("z^2 + c/z", z) == Ok(z * z + c / z));
+    assert!(
+        evaluate("-z^2", z) == Ok(-(z * z)),
+        "Negation binds tighter than ^."
+    );
+    assert!(
+        evaluate("2^3^2", z) == Ok(Complex::new(512.0, 0.0)),
+        "^ is not right-associative."
+    );
+    assert!(evaluate("1 - 2 - 3", z) == Ok(Complex::new(-4.0, 0.0)));
+    assert!(evaluate("|z| + 2i", z) == Ok(Complex::new(z.abs(), 2.0)));
+    assert!(evaluate("z^1.5", z) == Ok(z.powf(1.5)));
+    assert!(evaluate("z^i", z) == Ok(z.powc(Complex::I)));
+    assert!(evaluate("conj(z) * 1e-1", z) == Ok(z.conj() * 0.1));
+}
+
+#[test]
+fn test_formula_errors() {
+    let z = Complex::ZERO;
+    let message = |source: &str| evaluate(source, z).unwrap_err().to_string();
+
+    assert!(message("z^2 + q") == "column 7: unknown variable 'q'");
+    assert!(message("foo(z)") == "column 1: unknown function 'foo'");
+    assert!(message("(z + 1") == "column 7: expected ')'");
+    assert!(message("z +") == "column 4: unexpected end of formula");
+    assert!(message("z $ 1") == "column 3: unexpected character '$'");
+    assert!(message("z > 2") == "column 1: expected a number but found a condition");
+    assert!(message("sin(z > 1)").contains("expected a number"));
+    assert!(message("z z") == "column 3: unexpected text after the formula");
+
+    let params = [("z".to_string(), Complex::ONE)];
+    assert!(Formula::new(&FormulaSource::default(), &params).is_err());
+    for name in CONSTANTS {
+        let params = [(name.to_string(), Complex::ONE)];
+        assert!(
+            matches!(
+                Formula::new(&FormulaSource::default(), &params),
+                Err(err) if err.contains("built-in constant")
+            ),
+            "A parameter hiding a constant was accepted."
+        );
+    }
+    let bad_bailout = FormulaSource {
+        bailout: "z + 1",
+        ..FormulaSource::default()
+    };
+    assert!(Formula::new(&bad_bailout, &[]).is_err());
+    let radius_in_step = FormulaSource {
+        step: "z^2 + c + bailout",
+        ..FormulaSource::default()
+    };
+    assert!(matches!(
+        Formula::new(&radius_in_step, &[]),
+        Err(err) if err.contains("only the bailout formula may use 'bailout'")
+    ));
+    let z_in_constant = FormulaSource {
+        c: "pixel + z",
+        ..FormulaSource::default()
+    };
+    assert!(
+        matches!(
+            Formula::new(&z_in_constant, &[]),
+            Err(err) if err.contains("the constant cannot use 'z'")
+        ),
+        "A constant using z was accepted."
+    );
+}
+
+#[test]
+fn test_formula_fractal() {
+    use crate::fractal::{iterate, Mandelbrot};
+
+    // The default formula is the Mandelbrot set.
+    let formula = Formula::new(&FormulaSource::default(), &[]).unwrap();
+    for (re, im) in [(0.3, 0.5), (-0.75, 0.1), (-2.0, 1.5)] {
+        let pixel = Complex::new(re, im);
+        let expected = iterate(&Mandelbrot, pixel, 2.0, 200);
+        let found = iterate(&formula, pixel, 2.0, 200);
+        assert!(found.iterations == expected.iterations);
+    }
+
+    let source = FormulaSource {
+        step: "z^2 + p * pixel",
+        init: "pixel",
+        c: "0",
+        bailout: "re(z) > 10 || im(z) > 10",
+    };
+    let params = [("p".to_string(), Complex::new(2.0, 0.0))];
+    let formula = Formula::new(&source, &params).unwrap();
+    let escape = iterate(&formula, Complex::new(1.0, 0.0), 2.0, 100);
+    // 1 -> 3 -> 11
+    assert!(escape.iterations == 2 && escape.z == Complex::new(11.0, 0.0));
+
+    // Iterating through `next` sees the pixel as well.
+    let pixel = Complex::new(0.2, -0.3);
+    let escape = iterate(&formula, pixel, 2.0, 5);
+    let mut orbit = formula.orbit(pixel);
+    for _ in 0..5 {
+        orbit.advance(formula.next(&orbit));
+    }
+    assert!(
+        escape.iterations == 5 && orbit.z == escape.z,
+        "`next` lost the pixel."
+    );
+}
@@ -0,0 +1,861 @@
+use crate::Complex;
+
+/// An escape-time fractal: every pixel starts an orbit that is iterated
+/// until it escapes or the iteration limit is reached.
+pub trait Fractal: Sync {
+    /// The initial `z` and the constant `c` of the orbit for `pixel`.
+    fn start(&self, pixel: Complex) -> (Complex, Complex);
+
+    /// The state of the orbit for `pixel` before the first step. The
+    /// previous `z` starts at zero.
+    fn orbit(&self, pixel: Complex) -> Orbit {
+        let (z, c) = self.start(pixel);
+        Orbit {
+            z,
+            previous: Complex::ZERO,
+            c,
+            pixel,
+        }
+    }
+
+    /// One step of the orbit.
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        z * z + c
+    }
+
+    /// The next `z` given the whole state of the orbit, for recurrences that
+    /// look further back than the current `z`.
+    fn next(&self, orbit: &Orbit) -> Complex {
+        self.step(orbit.z, orbit.c)
+    }
+
+    /// How fast `|z|` grows once the orbit is far out: `|z|` is raised to
+    /// roughly this power every step. Used by smooth coloring.
+    fn degree(&self) -> f64 {
+        2.0
+    }
+
+    /// The test that tells when the orbit has escaped.
+    fn bailout(&self) -> Bailout {
+        Bailout::Modulus
+    }
+
+    /// Iterates the orbit of `pixel` until it leaves the bailout of the
+    /// fractal with radius `sqrt(bailout_sqr)` or `max_iterations` steps have
+    /// been taken.
+    fn escape(&self, pixel: Complex, bailout_sqr: f64, max_iterations: usize) -> Escape {
+        let bailout = self.bailout();
+        let mut orbit = self.orbit(pixel);
+        let mut iterations = 0;
+        while bailout.bounded(orbit.z, bailout_sqr) && iterations < max_iterations {
+            orbit.advance(self.next(&orbit));
+            iterations += 1;
+        }
+        Escape {
+            iterations,
+            z: orbit.z,
+        }
+    }
+
+    /// Like [`Fractal::escape`], calling `visit` with the state of the orbit
+    /// after every step, for colorings that look at the whole orbit.
+    fn escape_with(
+        &self,
+        pixel: Complex,
+        bailout_sqr: f64,
+        max_iterations: usize,
+        visit: &mut dyn FnMut(&Orbit),
+    ) -> Escape {
+        let bailout = self.bailout();
+        let mut orbit = self.orbit(pixel);
+        let mut iterations = 0;
+        while bailout.bounded(orbit.z, bailout_sqr) && iterations < max_iterations {
+            orbit.advance(self.next(&orbit));
+            iterations += 1;
+            visit(&orbit);
+        }
+        Escape {
+            iterations,
+            z: orbit.z,
+        }
+    }
+}
+
+/// The state of an orbit carried from step to step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Orbit {
+    pub z: Complex,
+    /// `z` before the last step.
+    pub previous: Complex,
+    pub c: Complex,
+    /// The pixel the orbit started from.
+    pub pixel: Complex,
+}
+
+impl Orbit {
+    /// Moves on to the next `z`.
+    pub fn advance(&mut self, z: Complex) {
+        self.previous = self.z;
+        self.z = z;
+    }
+}
+
+/// Bailout radius of the plain escape-time test.
+pub const DEFAULT_BAILOUT: f64 = 2.0;
+
+/// Bailout radius used with smooth coloring. A large radius makes the
+/// normalized iteration count independent of where exactly the orbit escaped.
+pub const SMOOTH_BAILOUT: f64 = 256.0;
+
+/// Bound of Pickover's biomorph test.
+pub const BIOMORPH_BAILOUT: f64 = 10.0;
+
+/// Bound for the tests of transcendental maps, which do not escape for sure
+/// until `exp` of the part being tested is huge.
+pub const TRANSCENDENTAL_BAILOUT: f64 = 50.0;
+
+/// How an orbit escapes, given the radius `r`:
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bailout {
+    /// `|z| > r`
+    Modulus,
+    /// `re z > r`, for maps driven by `exp(z)`.
+    Real,
+    /// `|re z| > r`, for maps driven by `cosh(z)`.
+    AbsReal,
+    /// `|im z| > r`, for maps driven by `sin(z)` and `cos(z)`.
+    AbsImaginary,
+    /// `|re z| > r` or `|im z| > r`, Pickover's biomorph test.
+    Components,
+}
+
+impl Bailout {
+    /// Whether `z` is still inside the bailout with radius
+    /// `sqrt(bailout_sqr)`. Orbits that overflowed to NaN are outside.
+    pub fn bounded(self, z: Complex, bailout_sqr: f64) -> bool {
+        match self {
+            Bailout::Modulus => z.norm_sqr() <= bailout_sqr,
+            Bailout::Real => z.re <= 0.0 || z.re * z.re <= bailout_sqr,
+            Bailout::AbsReal => z.re * z.re <= bailout_sqr,
+            Bailout::AbsImaginary => z.im * z.im <= bailout_sqr,
+            Bailout::Components => z.re * z.re <= bailout_sqr && z.im * z.im <= bailout_sqr,
+        }
+    }
+
+    /// The radius used unless another is given.
+    pub fn default_radius(self, smooth: bool) -> f64 {
+        match self {
+            Bailout::Modulus if smooth => SMOOTH_BAILOUT,
+            Bailout::Modulus => DEFAULT_BAILOUT,
+            Bailout::Components => BIOMORPH_BAILOUT,
+            _ => TRANSCENDENTAL_BAILOUT,
+        }
+    }
+}
+
+/// The state of an orbit when iteration stopped.
+pub struct Escape {
+    pub iterations: usize,
+    pub z: Complex,
+}
+
+impl Escape {
+    pub fn escaped(&self, max_iterations: usize) -> bool {
+        self.iterations < max_iterations
+    }
+
+    /// The normalized iteration count `n + 1 - log_d(ln|z|)` for a fractal
+    /// of the given `degree` `d`, a continuous version of `iterations` for
+    /// orbits that escaped a large bailout radius.
+    pub fn smooth(&self, max_iterations: usize, degree: f64) -> f64 {
+        if !self.escaped(max_iterations) {
+            return max_iterations as f64;
+        }
+        if degree <= 1.0 {
+            return self.iterations as f64;
+        }
+        self.iterations as f64 + 1.0 - self.z.abs().ln().ln() / degree.ln()
+    }
+
+    /// How far the orbit got past the bailout radius `r` on its last step,
+    /// `1 - log_d(ln|z| / ln r)` for a fractal of the given `degree` `d`:
+    /// 1 when `|z|` just passed `r`, and 0 when it reached `r^d`. Orbits
+    /// that did not escape count as 1.
+    pub fn fraction(&self, max_iterations: usize, bailout: f64, degree: f64) -> f64 {
+        if !self.escaped(max_iterations) || degree <= 1.0 || bailout <= 1.0 {
+            return 1.0;
+        }
+        let fraction = 1.0 - (self.z.abs().ln() / bailout.ln()).ln() / degree.ln();
+        fraction.clamp(0.0, 1.0)
+    }
+}
+
+/// Iterates the orbit of `pixel` until `|z|` exceeds `bailout` or
+/// `max_iterations` steps have been taken.
+pub fn iterate(
+    fractal: &dyn Fractal,
+    pixel: Complex,
+    bailout: f64,
+    max_iterations: usize,
+) -> Escape {
+    fractal.escape(pixel, bailout * bailout, max_iterations)
+}
+
+/// The `z^2 + c` loop on plain floats, sharing the squared components
+/// between the bailout test and the next step. Gives the same orbit as
+/// `z * z + c`.
+fn escape_quadratic(z: Complex, c: Complex, bailout_sqr: f64, max_iterations: usize) -> Escape {
+    let (mut x, mut y) = (z.re, z.im);
+    let (mut x2, mut y2) = (x * x, y * y);
+    let mut iterations = 0;
+
+    while x2 + y2 <= bailout_sqr && iterations < max_iterations {
+        y = 2.0 * x * y + c.im;
+        x = x2 - y2 + c.re;
+        x2 = x * x;
+        y2 = y * y;
+        iterations += 1;
+    }
+
+    Escape {
+        iterations,
+        z: Complex::new(x, y),
+    }
+}
+
+/// `z = z^2 + c`, with `z` starting at zero and `c` taken from the pixel.
+pub struct Mandelbrot;
+
+impl Fractal for Mandelbrot {
+    fn start(&self, pixel: Complex) -> (Complex, Complex) {
+        (Complex::new(0.0, 0.0), pixel)
+    }
+
+    fn escape(&self, pixel: Complex, bailout_sqr: f64, max_iterations: usize) -> Escape {
+        let (z, c) = self.start(pixel);
+        escape_quadratic(z, c, bailout_sqr, max_iterations)
+    }
+}
+
+/// `z = z^2 + c`, with `z` starting at the pixel and a fixed `c`.
+pub struct Julia {
+    pub c: Complex,
+}
+
+impl Fractal for Julia {
+    fn start(&self, pixel: Complex) -> (Complex, Complex) {
+        (pixel, self.c)
+    }
+
+    fn escape(&self, pixel: Complex, bailout_sqr: f64, max_iterations: usize) -> Escape {
+        let (z, c) = self.start(pixel);
+        escape_quadratic(z, c, bailout_sqr, max_iterations)
+    }
+}
+
+/// Variations of `z^2 + c` that fold or conjugate parts of `z`. With
+/// `z = x + iy` and `c = a + ib`, the next `z` is:
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Variant {
+    /// `(|x| + i|y|)^2 + c`
+    BurningShip,
+    /// `conj(z)^2 + c`, also known as the Mandelbar set.
+    Tricorn,
+    /// `|x^2 - y^2| + 2ixy + c`
+    Celtic,
+    /// `|x^2 - y^2| - 2i|xy| + c`
+    Buffalo,
+    /// `x^2 - y^2 - 2i|x|y + c`
+    Perpendicular,
+    /// `x^2 - y^2 + 2i|x|y + c`
+    Heart,
+}
+
+impl Variant {
+    pub fn step(self, z: Complex, c: Complex) -> Complex {
+        let (x, y) = (z.re, z.im);
+        let (re, im) = match self {
+            Variant::BurningShip => (x * x - y * y, 2.0 * (x * y).abs()),
+            Variant::Tricorn => (x * x - y * y, -2.0 * x * y),
+            Variant::Celtic => ((x * x - y * y).abs(), 2.0 * x * y),
+            Variant::Buffalo => ((x * x - y * y).abs(), -2.0 * (x * y).abs()),
+            Variant::Perpendicular => (x * x - y * y, -2.0 * x.abs() * y),
+            Variant::Heart => (x * x - y * y, 2.0 * x.abs() * y),
+        };
+        Complex::new(re + c.re, im + c.im)
+    }
+}
+
+/// A [`Variant`] in its Mandelbrot flavor, or its Julia flavor when `julia`
+/// holds the constant `c`.
+pub struct Variation {
+    pub variant: Variant,
+    pub julia: Option<Complex>,
+}
+
+impl Fractal for Variation {
+    fn start(&self, pixel: Complex) -> (Complex, Complex) {
+        match self.julia {
+            Some(c) => (pixel, c),
+            None => (Complex::new(0.0, 0.0), pixel),
+        }
+    }
+
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        self.variant.step(z, c)
+    }
+}
+
+/// The power `d` in `z^d + c`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Exponent {
+    /// Computed by repeated multiplication.
+    Int(i32),
+    /// Computed in polar form, like the complex case.
+    Real(f64),
+    Complex(Complex),
+}
+
+impl Exponent {
+    /// The simplest representation of `d`.
+    pub fn new(d: Complex) -> Self {
+        if d.im != 0.0 {
+            Exponent::Complex(d)
+        } else if d.re.fract() == 0.0 && d.re.abs() <= i32::MAX as f64 {
+            Exponent::Int(d.re as i32)
+        } else {
+            Exponent::Real(d.re)
+        }
+    }
+
+    fn real_part(self) -> f64 {
+        match self {
+            Exponent::Int(n) => n as f64,
+            Exponent::Real(p) => p,
+            Exponent::Complex(w) => w.re,
+        }
+    }
+}
+
+/// The Multibrot set `z = z^d + c`, or its Multi-Julia flavor when `julia`
+/// holds the constant `c`.
+pub struct Multibrot {
+    pub power: Exponent,
+    pub julia: Option<Complex>,
+}
+
+impl Fractal for Multibrot {
+    fn start(&self, pixel: Complex) -> (Complex, Complex) {
+        match self.julia {
+            Some(c) => (pixel, c),
+            // Zero would blow up at once with a negative power, so start
+            // one step later.
+            None if self.power.real_part() <= 0.0 => (pixel, pixel),
+            None => (Complex::new(0.0, 0.0), pixel),
+        }
+    }
+
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        match self.power {
+            Exponent::Int(n) => z.powi(n) + c,
+            Exponent::Real(p) => z.powf(p) + c,
+            Exponent::Complex(w) => z.powc(w) + c,
+        }
+    }
+
+    fn degree(&self) -> f64 {
+        self.power.real_part()
+    }
+}
+
+/// Recurrences beyond `z^2 + c`. In their Mandelbrot flavor `c` is the
+/// pixel; the next `z` is:
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Recurrence {
+    /// `z^2 + c + p * z_prev`
+    Phoenix { p: Complex },
+    /// `((z^2 + c - 1) / (2z + c - 2))^2`
+    MagnetI,
+    /// `((z^3 + 3(c - 1)z + (c - 1)(c - 2)) / (3z^2 + 3(c - 2)z + (c - 1)(c - 2) + 1))^2`
+    MagnetII,
+    /// `c * z * (1 - z)`, starting from the critical point 1/2.
+    Lambda,
+    /// `z^2 + z_prev + c`, starting with `z = z_prev = pixel`.
+    Manowar,
+}
+
+impl Recurrence {
+    pub fn next(self, orbit: &Orbit) -> Complex {
+        let Orbit { z, previous, c, .. } = *orbit;
+        match self {
+            Recurrence::Phoenix { p } => z * z + c + p * previous,
+            Recurrence::MagnetI => {
+                let q = (z * z + c - 1.0) / (z * 2.0 + c - 2.0);
+                q * q
+            }
+            Recurrence::MagnetII => {
+                let (c1, c2) = (c - 1.0, c - 2.0);
+                let numerator = z * z * z + c1 * z * 3.0 + c1 * c2;
+                let denominator = z * z * 3.0 + c2 * z * 3.0 + c1 * c2 + 1.0;
+                let q = numerator / denominator;
+                q * q
+            }
+            Recurrence::Lambda => c * z * (1.0 - z),
+            Recurrence::Manowar => z * z + previous + c,
+        }
+    }
+}
+
+/// A [`Recurrence`] in its Mandelbrot flavor, or its Julia flavor when
+/// `julia` holds the constant `c`.
+pub struct RecurrenceSet {
+    pub recurrence: Recurrence,
+    pub julia: Option<Complex>,
+}
+
+impl Fractal for RecurrenceSet {
+    fn start(&self, pixel: Complex) -> (Complex, Complex) {
+        match (self.julia, self.recurrence) {
+            (Some(c), _) => (pixel, c),
+            (None, Recurrence::Lambda) => (Complex::new(0.5, 0.0), pixel),
+            (None, Recurrence::Manowar) => (pixel, pixel),
+            (None, _) => (Complex::ZERO, pixel),
+        }
+    }
+
+    fn orbit(&self, pixel: Complex) -> Orbit {
+        let (z, c) = self.start(pixel);
+        let previous = match self.recurrence {
+            Recurrence::Manowar => pixel,
+            _ => Complex::ZERO,
+        };
+        Orbit {
+            z,
+            previous,
+            c,
+            pixel,
+        }
+    }
+
+    fn next(&self, orbit: &Orbit) -> Complex {
+        self.recurrence.next(orbit)
+    }
+}
+
+/// A fractal with Pickover's biomorph test, [`Bailout::Components`], in
+/// place of its own.
+pub struct Biomorph<'a>(pub &'a dyn Fractal);
+
+impl Fractal for Biomorph<'_> {
+    fn start(&self, pixel: Complex) -> (Complex, Complex) {
+        self.0.start(pixel)
+    }
+
+    fn orbit(&self, pixel: Complex) -> Orbit {
+        self.0.orbit(pixel)
+    }
+
+    fn next(&self, orbit: &Orbit) -> Complex {
+        self.0.next(orbit)
+    }
+
+    fn degree(&self) -> f64 {
+        self.0.degree()
+    }
+
+    fn bailout(&self) -> Bailout {
+        Bailout::Components
+    }
+}
+
+/// Maps built on `exp` and its relatives. In their Mandelbrot flavor `c` is
+/// the pixel and `z` starts at the critical or asymptotic value, so that the
+/// first step gives `c`. The next `z` is:
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transcendental {
+    /// `c * exp(z)`, escaping when `re z > r`.
+    Exp,
+    /// `c * sin(z)`, escaping when `|im z| > r`.
+    Sin,
+    /// `c * cos(z)`, escaping when `|im z| > r`.
+    Cos,
+    /// `c * cosh(z)`, escaping when `|re z| > r`.
+    Cosh,
+    /// `z^2 + cosh(z) + c`, escaping when `|re z| > r`.
+    QuadraticCosh,
+}
+
+impl Transcendental {
+    pub fn step(self, z: Complex, c: Complex) -> Complex {
+        match self {
+            Transcendental::Exp => c * z.exp(),
+            Transcendental::Sin => c * z.sin(),
+            Transcendental::Cos => c * z.cos(),
+            Transcendental::Cosh => c * z.cosh(),
+            Transcendental::QuadraticCosh => z * z + z.cosh() + c,
+        }
+    }
+
+    /// Where the orbit of the Mandelbrot flavor starts.
+    fn critical(self) -> Complex {
+        match self {
+            Transcendental::Sin => Complex::new(std::f64::consts::FRAC_PI_2, 0.0),
+            _ => Complex::ZERO,
+        }
+    }
+
+    pub fn bailout(self) -> Bailout {
+        match self {
+            Transcendental::Exp => Bailout::Real,
+            Transcendental::Sin | Transcendental::Cos => Bailout::AbsImaginary,
+            Transcendental::Cosh | Transcendental::QuadraticCosh => Bailout::AbsReal,
+        }
+    }
+}
+
+/// A [`Transcendental`] map in its Mandelbrot flavor, or its Julia flavor
+/// when `julia` holds the constant `c`.
+pub struct TranscendentalSet {
+    pub function: Transcendental,
+    pub julia: Option<Complex>,
+}
+
+impl Fractal for TranscendentalSet {
+    fn start(&self, pixel: Complex) -> (Complex, Complex) {
+        match self.julia {
+            Some(c) => (pixel, c),
+            None => (self.function.critical(), pixel),
+        }
+    }
+
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        self.function.step(z, c)
+    }
+
+    /// Escaped orbits grow too fast for the normalized iteration count, so
+    /// smooth coloring keeps the plain count.
+    fn degree(&self) -> f64 {
+        1.0
+    }
+
+    fn bailout(&self) -> Bailout {
+        self.function.bailout()
+    }
+}
+
+#[test]
+fn test_fractals() {
+    let pixel = Complex::new(0.25, -0.5);
+    let c = Complex::new(-0.4, 0.5868);
+
+    assert!(Mandelbrot.start(pixel) == (Complex::new(0.0, 0.0), pixel));
+    assert!(Julia { c }.start(pixel) == (pixel, c));
+    assert!(Julia { c }.step(pixel, c) == pixel * pixel + c);
+}
+
+#[test]
+fn test_variants() {
+    let z = Complex::new(-0.7, 0.3);
+    let c = Complex::new(0.1, -0.2);
+    let square = |z: Complex| z * z + c;
+
+    assert!(Variant::Tricorn.step(z, c) == square(z.conj()));
+    assert!(Variant::BurningShip.step(z, c) == square(Complex::new(z.re.abs(), z.im.abs())));
+    assert!(Variant::Celtic.step(z, c) == Complex::new((z * z).re.abs() + c.re, (z * z).im + c.im));
+    assert!(Variant::Perpendicular.step(z, c).im == -Variant::Heart.step(z, -c).im);
+    let buffalo = Variant::Buffalo.step(z, c);
+    assert!(
+        buffalo.re >= c.re && buffalo.im <= c.im,
+        "Buffalo does not fold."
+    );
+
+    let julia = Variation {
+        variant: Variant::Celtic,
+        julia: Some(c),
+    };
+    assert!(julia.start(z) == (z, c));
+}
+
+#[test]
+fn test_smooth() {
+    let max_iterations = 100;
+    let run = |re, im| {
+        iterate(
+            &Mandelbrot,
+            Complex::new(re, im),
+            SMOOTH_BAILOUT,
+            max_iterations,
+        )
+    };
+
+    assert!(run(0.0, 0.0).smooth(max_iterations, 2.0) == max_iterations as f64);
+
+    // Neighbouring points must not jump by whole iterations the way the
+    // plain count does.
+    let (a, b) = (run(0.5, 0.5), run(0.5, 0.5001));
+    assert!(a.escaped(max_iterations) && b.escaped(max_iterations));
+    assert!(
+        (a.smooth(max_iterations, 2.0) - b.smooth(max_iterations, 2.0)).abs() < 0.01,
+        "The smooth iteration count is not continuous."
+    );
+}
+
+#[test]
+fn test_escape_quadratic() {
+    // The generic loop of the trait, without the fast path.
+    struct Plain;
+    impl Fractal for Plain {
+        fn start(&self, pixel: Complex) -> (Complex, Complex) {
+            Mandelbrot.start(pixel)
+        }
+    }
+
+    for (re, im) in [(0.3, 0.5), (-0.75, 0.1), (0.25, 0.0), (-2.0, 1.5)] {
+        let fast = iterate(&Mandelbrot, Complex::new(re, im), 2.0, 500);
+        let plain = iterate(&Plain, Complex::new(re, im), 2.0, 500);
+        assert!(
+            fast.iterations == plain.iterations && fast.z == plain.z,
+            "The fast path diverged from z * z + c at {re}+{im}i."
+        );
+    }
+}
+
+/// Checks that the fast path of the escape loop is no slower than either the
+/// original loop, which called `abs` every iteration, or the generic loop of
+/// the trait, for the same iteration counts. Timings are too noisy for every
+/// test run, so run it with
+/// `cargo test --release bench_escape -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_escape() {
+    use std::hint::black_box;
+    use std::time::{Duration, Instant};
+
+    fn original(pixel: Complex, max_iterations: usize) -> usize {
+        let mut iterations = 0;
+        let (mut z, c) = (Complex::new(0.0, 0.0), pixel);
+        while z.abs() <= 2_f64 && iterations < max_iterations {
+            z = z * z + c;
+            iterations += 1;
+        }
+        iterations
+    }
+
+    struct Plain;
+    impl Fractal for Plain {
+        fn start(&self, pixel: Complex) -> (Complex, Complex) {
+            Mandelbrot.start(pixel)
+        }
+    }
+
+    let pixels = (0..256 * 256)
+        .map(|i| {
+            Complex::new(
+                -2.0 + (i % 256) as f64 / 96.0,
+                -1.3 + (i / 256) as f64 / 96.0,
+            )
+        })
+        .collect::<Vec<_>>();
+    let loops: [&dyn Fn(Complex) -> usize; 3] = [
+        &|pixel| original(pixel, 1000),
+        &|pixel| iterate(&Plain, pixel, 2.0, 1000).iterations,
+        &|pixel| iterate(&Mandelbrot, pixel, 2.0, 1000).iterations,
+    ];
+    // The loops take turns, and each keeps its best run, which is the least
+    // disturbed by other work.
+    let mut best = [Duration::MAX; 3];
+    let mut totals = [0; 3];
+    for _ in 0..9 {
+        for (i, f) in loops.iter().enumerate() {
+            let start = Instant::now();
+            totals[i] = black_box(black_box(&pixels).iter().map(|&pixel| f(pixel)).sum());
+            best[i] = best[i].min(start.elapsed());
+        }
+    }
+    let [abs, generic, fast] = best;
+    assert!(
+        totals[0] == totals[1] && totals[0] == totals[2],
+        "The loops disagree on the iteration counts."
+    );
+    println!(
+        "abs loop: {abs:?}, generic loop: {generic:?}, fast path: {fast:?}, speedup {:.2}x",
+        abs.as_secs_f64() / fast.as_secs_f64()
+    );
+    // Optimized builds gain only a few percent, so allow for that much noise.
+    let no_slower = |other: Duration| fast.as_secs_f64() <= 1.05 * other.as_secs_f64();
+    assert!(no_slower(abs), "The fast path is slower than the abs loop.");
+    assert!(
+        no_slower(generic),
+        "The fast path is slower than the generic loop."
+    );
+}
+
+#[test]
+fn test_multibrot() {
+    assert!(Exponent::new(Complex::new(3.0, 0.0)) == Exponent::Int(3));
+    assert!(Exponent::new(Complex::new(2.5, 0.0)) == Exponent::Real(2.5));
+    assert!(Exponent::new(Complex::new(2.0, 0.1)) == Exponent::Complex(Complex::new(2.0, 0.1)));
+
+    let z = Complex::new(0.3, -0.4);
+    let c = Complex::new(-0.1, 0.2);
+    let multibrot = |power| Multibrot { power, julia: None };
+    assert!(multibrot(Exponent::Int(3)).step(z, c) == z * z * z + c);
+    let polar = multibrot(Exponent::Real(3.0)).step(z, c);
+    assert!(
+        (polar - (z * z * z + c)).abs() < 1e-12,
+        "The polar form is off."
+    );
+    assert!(multibrot(Exponent::Int(2)).start(z) == Mandelbrot.start(z));
+
+    // Smooth values must be continuous for higher degrees too.
+    let cubic = multibrot(Exponent::Int(3));
+    let run = |im| iterate(&cubic, Complex::new(0.7, im), SMOOTH_BAILOUT, 100);
+    let (a, b) = (run(0.5), run(0.50001));
+    assert!(a.iterations != 100 && b.iterations != 100);
+    assert!(
+        (a.smooth(100, cubic.degree()) - b.smooth(100, cubic.degree())).abs() < 0.01,
+        "The smooth iteration count depends on the wrong degree."
+    );
+}
+
+#[test]
+fn test_recurrences() {
+    let (z, previous, c) = (
+        Complex::new(0.3, -0.2),
+        Complex::new(-0.1, 0.4),
+        Complex::new(0.25, 0.5),
+    );
+    let orbit = Orbit {
+        z,
+        previous,
+        c,
+        pixel: c,
+    };
+    let p = Complex::new(-0.5, 0.0);
+    assert!(Recurrence::Phoenix { p }.next(&orbit) == z * z + c + p * previous);
+    assert!(Recurrence::Manowar.next(&orbit) == z * z + previous + c);
+    assert!(Recurrence::Lambda.next(&orbit) == c * z * (1.0 - z));
+    // Both magnets have the fixed point 1.
+    let one = Orbit {
+        z: Complex::ONE,
+        ..orbit
+    };
+    assert!((Recurrence::MagnetI.next(&one) - Complex::ONE).abs() < 1e-12);
+    assert!((Recurrence::MagnetII.next(&one) - Complex::ONE).abs() < 1e-12);
+
+    // Without feedback Phoenix is the Mandelbrot set.
+    let phoenix = RecurrenceSet {
+        recurrence: Recurrence::Phoenix { p: Complex::ZERO },
+        julia: None,
+    };
+    for pixel in [Complex::new(-0.75, 0.1), Complex::new(0.3, 0.6)] {
+        let a = phoenix.escape(pixel, 4.0, 100);
+        let b = Mandelbrot.escape(pixel, 4.0, 100);
+        assert!(a.iterations == b.iterations && a.z == b.z);
+    }
+
+    // The default loop carries the previous z along.
+    let manowar = RecurrenceSet {
+        recurrence: Recurrence::Manowar,
+        julia: None,
+    };
+    let pixel = Complex::new(0.1, 0.1);
+    let mut orbit = manowar.orbit(pixel);
+    assert!(
+        orbit
+            == Orbit {
+                z: pixel,
+                previous: pixel,
+                c: pixel,
+                pixel
+            }
+    );
+    for _ in 0..3 {
+        orbit.advance(manowar.next(&orbit));
+    }
+    let z1 = pixel * pixel + pixel + pixel;
+    let z2 = z1 * z1 + pixel + pixel;
+    let z3 = z2 * z2 + z1 + pixel;
+    assert!(orbit.z == z3 && orbit.previous == z2);
+    assert!(manowar.escape(pixel, 1e6, 3).z == z3);
+}
+
+#[test]
+fn test_transcendental() {
+    let (z, c) = (Complex::new(0.3, -0.2), Complex::new(0.5, 0.1));
+    assert!(Transcendental::Exp.step(z, c) == c * z.exp());
+    assert!(Transcendental::QuadraticCosh.step(z, c) == z * z + z.cosh() + c);
+    let mandelbrot = |function| TranscendentalSet {
+        function,
+        julia: None,
+    };
+    for function in [
+        Transcendental::Exp,
+        Transcendental::Sin,
+        Transcendental::Cos,
+    ] {
+        let (z, c) = mandelbrot(function).start(Complex::new(0.4, 0.2));
+        assert!(
+            (function.step(z, c) - c).abs() < 1e-12,
+            "{function:?} does not start at its critical value."
+        );
+    }
+
+    // Below 1/e, c * exp(z) has an attracting fixed point; above it, the
+    // orbit of 0 runs off to the right.
+    let exp = mandelbrot(Transcendental::Exp);
+    assert!(!exp.escape(Complex::new(0.3, 0.0), 2500.0, 200).escaped(200));
+    let escape = exp.escape(Complex::new(1.0, 0.0), 2500.0, 200);
+    assert!(escape.escaped(200) && escape.z.re > 50.0);
+
+    // A large imaginary part sends sin(z) off even though |z| stays small
+    // for the modulus test with its usual radius.
+    let sin = TranscendentalSet {
+        function: Transcendental::Sin,
+        julia: Some(Complex::new(1.0, 0.0)),
+    };
+    assert!(sin.escape(Complex::new(0.1, 0.0), 2500.0, 100).iterations == 100);
+    assert!(sin.escape(Complex::new(0.0, 3.0), 2500.0, 100).iterations < 5);
+
+    assert!(Bailout::Real.bounded(Complex::new(-1e9, 0.0), 1.0));
+    assert!(!Bailout::AbsReal.bounded(Complex::new(-2.0, 0.0), 1.0));
+    assert!(Bailout::AbsImaginary.bounded(Complex::new(1e9, 0.5), 1.0));
+    for bailout in [
+        Bailout::Modulus,
+        Bailout::Real,
+        Bailout::AbsReal,
+        Bailout::AbsImaginary,
+        Bailout::Components,
+    ] {
+        assert!(!bailout.bounded(Complex::new(f64::NAN, f64::NAN), 1.0));
+    }
+}
+
+#[test]
+fn test_escape_with() {
+    let pixel = Complex::new(-0.5, 0.6);
+    let mut visited = Vec::new();
+    let escape = Mandelbrot.escape_with(pixel, 4.0, 50, &mut |orbit| visited.push(orbit.z));
+    let plain = Mandelbrot.escape(pixel, 4.0, 50);
+    assert!(escape.iterations == plain.iterations && escape.z == plain.z);
+    assert!(visited.len() == escape.iterations && visited[0] == pixel);
+    assert!(visited[1] == pixel * pixel + pixel);
+
+    // The biomorph test stops as soon as one component is out.
+    let julia = Julia {
+        c: Complex::new(0.5, 0.0),
+    };
+    let biomorph = Biomorph(&julia);
+    let mut last = Complex::ZERO;
+    let escape = biomorph.escape_with(Complex::new(3.0, 0.0), 81.0, 50, &mut |orbit| {
+        last = orbit.z
+    });
+    assert!(escape.iterations == 1 && last == Complex::new(9.5, 0.0));
+    assert!(julia.escape(Complex::new(8.0, 8.0), 100.0, 50).iterations == 0);
+    assert!(
+        biomorph
+            .escape(Complex::new(8.0, 8.0), 100.0, 50)
+            .iterations
+            == 1
+    );
+}
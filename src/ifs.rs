@@ -0,0 +1,329 @@
+use std::fs;
+
+use crate::cli::View;
+use crate::density::{self, Histogram};
+use crate::palette::Palette;
+use crate::random::Rng;
+use crate::{Complex, Draw, Image, Settings};
+
+/// The affine map `x' = a x + b y + e`, `y' = c x + d y + f`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Affine {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Affine {
+    pub fn apply(&self, p: Complex) -> Complex {
+        Complex::new(
+            self.a * p.re + self.b * p.im + self.e,
+            self.c * p.re + self.d * p.im + self.f,
+        )
+    }
+}
+
+/// An iterated function system: affine maps, each picked with its own
+/// probability.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ifs {
+    maps: Vec<Affine>,
+    /// Running sums of the probabilities, ending in 1.
+    cumulative: Vec<f64>,
+}
+
+pub const BUILTINS: [&str; 3] = ["dragon", "fern", "sierpinski"];
+
+impl Ifs {
+    /// The system of `maps` with `weights` relative to each other.
+    pub fn new(maps: Vec<Affine>, weights: &[f64]) -> Result<Self, String> {
+        if maps.is_empty() {
+            return Err("the system has no maps".to_string());
+        }
+        if weights.iter().any(|&w| !(w >= 0.0 && w.is_finite())) {
+            return Err("probabilities must not be negative".to_string());
+        }
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return Err("the probabilities add up to zero".to_string());
+        }
+        let cumulative = weights
+            .iter()
+            .scan(0.0, |sum, w| {
+                *sum += w / total;
+                Some(*sum)
+            })
+            .collect();
+        Ok(Self { maps, cumulative })
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        let maps: &[[f64; 7]] = match name {
+            // Barnsley's fern.
+            "fern" => &[
+                [0.0, 0.0, 0.0, 0.16, 0.0, 0.0, 0.01],
+                [0.85, 0.04, -0.04, 0.85, 0.0, 1.6, 0.85],
+                [0.2, -0.26, 0.23, 0.22, 0.0, 1.6, 0.07],
+                [-0.15, 0.28, 0.26, 0.24, 0.0, 0.44, 0.07],
+            ],
+            // Sierpinski's triangle on (0, 0), (1, 0) and (0.5, 1).
+            "sierpinski" => &[
+                [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0],
+                [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 1.0],
+                [0.5, 0.0, 0.0, 0.5, 0.25, 0.5, 1.0],
+            ],
+            // The Heighway dragon between (0, 0) and (1, 0).
+            "dragon" => &[
+                [0.5, -0.5, 0.5, 0.5, 0.0, 0.0, 1.0],
+                [-0.5, -0.5, 0.5, -0.5, 1.0, 0.0, 1.0],
+            ],
+            _ => return None,
+        };
+        Some(Self::from_rows(maps).unwrap())
+    }
+
+    /// A built-in system, a Fractint `.ifs` file, optionally followed by
+    /// `:name` to pick an entry, or maps given inline as seven numbers
+    /// `a b c d e f p` each, separated by `;`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if let Some(ifs) = Self::builtin(s) {
+            return Ok(ifs);
+        }
+        let (path, entry) = match s.rsplit_once(':') {
+            Some((path, entry)) if is_ifs_file(path) => (path, Some(entry)),
+            _ => (s, None),
+        };
+        if is_ifs_file(path) {
+            let text = fs::read_to_string(path)
+                .map_err(|err| format!("could not read '{path}': {err}"))?;
+            return parse_ifs(&text, entry).map_err(|err| format!("{path}: {err}"));
+        }
+        parse_maps(s)
+    }
+
+    fn from_rows(rows: &[[f64; 7]]) -> Result<Self, String> {
+        let maps = rows
+            .iter()
+            .map(|&[a, b, c, d, e, f, _]| Affine { a, b, c, d, e, f })
+            .collect();
+        let weights: Vec<f64> = rows.iter().map(|row| row[6]).collect();
+        Self::new(maps, &weights)
+    }
+
+    /// The map for `u`, a random number in `[0, 1)`.
+    pub fn choose(&self, u: f64) -> &Affine {
+        let i = self.cumulative.partition_point(|&sum| sum <= u);
+        &self.maps[i.min(self.maps.len() - 1)]
+    }
+}
+
+fn is_ifs_file(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".ifs")
+}
+
+/// Maps as numbers `a b c d e f p`, seven to a map, separated by white
+/// space, commas or semicolons.
+fn parse_maps(text: &str) -> Result<Ifs, String> {
+    let numbers = text
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|token| !token.is_empty())
+        .map(|token| {
+            token
+                .parse::<f64>()
+                .map_err(|_| format!("'{token}' is not a number"))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    if numbers.len() % 7 != 0 {
+        return Err("every map needs seven numbers, `a b c d e f p`".to_string());
+    }
+    let rows: Vec<[f64; 7]> = numbers
+        .chunks(7)
+        .map(|row| row.try_into().unwrap())
+        .collect();
+    Ifs::from_rows(&rows)
+}
+
+/// A Fractint `.ifs` file: entries `name { a b c d e f p ... }` with
+/// comments after `;`. Picks the entry called `entry`, or the first one.
+/// Entries marked `(3D)` are not supported.
+pub fn parse_ifs(text: &str, entry: Option<&str>) -> Result<Ifs, String> {
+    let text: Vec<&str> = text
+        .lines()
+        .map(|line| line.split(';').next().unwrap_or(""))
+        .collect();
+    let text = text.join("\n");
+
+    let mut rest = text.as_str();
+    while let Some(open) = rest.find('{') {
+        let header = rest[..open].trim();
+        let Some(length) = rest[open..].find('}') else {
+            return Err(format!("'{header}' is not closed"));
+        };
+        let body = &rest[open + 1..open + length];
+        rest = &rest[open + length + 1..];
+
+        let lower = header.to_ascii_lowercase();
+        let (name, three_d) = match lower.strip_suffix("(3d)") {
+            Some(name) => (header[..name.len()].trim(), true),
+            None => (header, false),
+        };
+        if entry.is_some_and(|entry| !entry.eq_ignore_ascii_case(name)) {
+            continue;
+        }
+        if three_d {
+            if entry.is_some() {
+                return Err(format!("'{name}' is a 3D system, which is not supported"));
+            }
+            continue;
+        }
+        return parse_maps(body).map_err(|err| format!("{name}: {err}"));
+    }
+    match entry {
+        Some(entry) => Err(format!("there is no system called '{entry}'")),
+        None => Err("there is no 2D system".to_string()),
+    }
+}
+
+/// Points of the chaos game played from one random start.
+const CHUNK: usize = 1 << 16;
+
+/// Steps taken before a chunk starts plotting, enough for any contracting
+/// system to reach its attractor within a pixel.
+const WARMUP: usize = 50;
+
+/// Draws the attractor of an [`Ifs`] with the chaos game: starting from any
+/// point, apply randomly chosen maps and plot where the point lands. The
+/// image is colored by the logarithm of the hit count.
+pub struct ChaosGame {
+    pub ifs: Ifs,
+    pub points: usize,
+    pub seed: u64,
+    pub palette: Palette,
+    /// The region to draw, or `None` to fit it to the attractor.
+    pub view: Option<View>,
+}
+
+impl ChaosGame {
+    /// The random stream of `chunk` and a point on the attractor reached
+    /// with it.
+    fn start(&self, chunk: usize) -> (Rng, Complex) {
+        let mut rng = Rng::stream(self.seed, chunk as u64);
+        let mut p = Complex::new(rng.next_f64(), rng.next_f64());
+        for _ in 0..WARMUP {
+            p = self.ifs.choose(rng.next_f64()).apply(p);
+        }
+        (rng, p)
+    }
+
+    /// The region the points of the first chunk fill, with a margin, in the
+    /// proportions of a `width` by `height` image.
+    pub fn fit(&self, width: usize, height: usize) -> View {
+        let (mut rng, mut p) = self.start(0);
+        let points = (0..CHUNK).map(|_| {
+            p = self.ifs.choose(rng.next_f64()).apply(p);
+            p
+        });
+        density::fit(points, width, height)
+    }
+
+    pub fn accumulate(&self, settings: &Settings) -> Histogram {
+        let (width, height) = (settings.width, settings.height);
+        let view = self.view.unwrap_or_else(|| self.fit(width, height));
+        let chunks = self.points.div_ceil(CHUNK);
+        let empty = || Histogram::upright(width, height, view);
+        density::accumulate(settings.threads, chunks, empty, |chunk, histogram| {
+            let (mut rng, mut p) = self.start(chunk);
+            for _ in chunk * CHUNK..self.points.min((chunk + 1) * CHUNK) {
+                p = self.ifs.choose(rng.next_f64()).apply(p);
+                histogram.plot(p);
+            }
+        })
+    }
+}
+
+impl Draw for ChaosGame {
+    fn draw(&self, settings: &Settings) -> Image {
+        self.accumulate(settings).log_image(&self.palette)
+    }
+}
+
+#[test]
+fn test_ifs() {
+    let ifs = Ifs::parse("0.5 0 0 0.5 0 0 1; 0.5 0 0 0.5 0.5 0 3").unwrap();
+    assert!(ifs.cumulative == [0.25, 1.0]);
+    assert!(ifs.choose(0.1).e == 0.0 && ifs.choose(0.25).e == 0.5 && ifs.choose(0.99).e == 0.5);
+    let map = ifs.choose(0.5);
+    assert!(map.apply(Complex::new(1.0, 2.0)) == Complex::new(1.0, 1.0));
+
+    assert!(Ifs::parse("1 2 3").is_err(), "A short map was accepted.");
+    assert!(
+        Ifs::parse("1 0 0 1 0 0 0").is_err(),
+        "Zero probabilities were accepted."
+    );
+    assert!(Ifs::parse("1 0 0 1 0 0 -1; 1 0 0 1 0 0 2").is_err());
+    for name in BUILTINS {
+        assert!(Ifs::parse(name) == Ok(Ifs::builtin(name).unwrap()));
+    }
+}
+
+#[test]
+fn test_ifs_file() {
+    let text = include_str!("../ifs/sample.ifs");
+    let binary = parse_ifs(text, None).unwrap();
+    assert!(binary.maps.len() == 3 && binary.maps[2].b == -0.5);
+    let fern = parse_ifs(text, Some("fern")).unwrap();
+    assert!(fern.maps == Ifs::builtin("fern").unwrap().maps);
+    assert!(parse_ifs(text, Some("Triangle")).unwrap().cumulative[2] == 1.0);
+    assert!(
+        parse_ifs(text, Some("fern3d")).is_err(),
+        "A 3D system was accepted."
+    );
+    assert!(parse_ifs(text, Some("maple")).is_err());
+    assert!(Ifs::parse("ifs/sample.ifs:triangle") == parse_ifs(text, Some("triangle")));
+}
+
+#[test]
+fn test_chaos_game() {
+    let mut settings = density::test_settings(1);
+    let game = ChaosGame {
+        ifs: Ifs::builtin("sierpinski").unwrap(),
+        points: CHUNK + 10,
+        seed: 9,
+        palette: Palette::builtin("gray").unwrap(),
+        view: Some(settings.view),
+    };
+    let one = game.accumulate(&settings);
+    settings.threads = 2;
+    assert!(one.counts == game.accumulate(&settings).counts);
+
+    // The middle of the triangle stays empty.
+    let pixel = |x: f64, y: f64| {
+        let (x, y) = (((x + 2.0) * 8.0) as usize, ((2.0 - y) * 8.0) as usize);
+        one.counts[y * 32 + x]
+    };
+    assert!(pixel(0.5, 0.3) == 0 && pixel(0.05, 0.02) > 0);
+
+    // The fern is fitted to the image and stands upright: its stem is in
+    // the bottom rows.
+    let fern = ChaosGame {
+        ifs: Ifs::builtin("fern").unwrap(),
+        view: None,
+        ..game
+    };
+    let view = fern.fit(32, 32);
+    assert!(view.from_y < 0.0 && view.to_y > 9.9 && view.to_y < 11.0);
+    let counts = fern.accumulate(&settings).counts;
+    let total: u64 = counts.iter().map(|&n| n as u64).sum();
+    assert!(
+        total > 99 * fern.points as u64 / 100,
+        "The fit cut off the fern."
+    );
+    let row = |y: usize| &counts[y * 32..(y + 1) * 32];
+    let stem = (-view.from_x / (view.to_x - view.from_x) * 32.0) as usize;
+    assert!(row(30)[stem] > 0, "The stem is not at the bottom.");
+    assert!(row(0).iter().all(|&n| n == 0) && row(31).iter().all(|&n| n == 0));
+}
@@ -0,0 +1,387 @@
+use std::fmt::Write;
+
+use crate::palette::Palette;
+use crate::{Complex, Draw, Image, Settings};
+
+/// Longest string an L-system may grow to.
+const MAX_SYMBOLS: usize = 1 << 22;
+
+/// Most lines an L-system may draw; each takes 32 bytes, and a few dozen
+/// more in the SVG document.
+const MAX_LINES: usize = 1 << 20;
+
+/// A Lindenmayer system drawn by a turtle. The turtle reads the expanded
+/// string: `F` and `G` draw a step forward, `f` and `g` move without
+/// drawing, `+` turns left and `-` right by `angle`, `|` turns around, and
+/// `[` and `]` save and restore the position and heading. Other symbols
+/// only take part in the rules.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LSystem {
+    pub axiom: String,
+    /// Replacements for symbols; symbols without a rule stay as they are.
+    pub rules: Vec<(char, String)>,
+    /// Turning angle in degrees.
+    pub angle: f64,
+    /// Initial heading in degrees, counterclockwise from the right.
+    pub heading: f64,
+    /// Number of times the rules are applied.
+    pub depth: usize,
+}
+
+pub const BUILTINS: [&str; 5] = ["dragon", "gosper", "hilbert", "koch", "plant"];
+
+impl LSystem {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let (axiom, rules, angle, heading, depth): (_, &[(char, &str)], _, _, _) = match name {
+            // The Koch snowflake.
+            "koch" => ("F--F--F", &[('F', "F+F--F+F")], 60.0, 0.0, 4),
+            "hilbert" => (
+                "X",
+                &[('X', "+YF-XFX-FY+"), ('Y', "-XF+YFY+FX-")],
+                90.0,
+                0.0,
+                6,
+            ),
+            // The flowsnake.
+            "gosper" => (
+                "F",
+                &[('F', "F-G--G+F++FF+G-"), ('G', "+F-FG--G-F++F+G")],
+                60.0,
+                0.0,
+                4,
+            ),
+            // The Heighway dragon.
+            "dragon" => ("F", &[('F', "F+G"), ('G', "F-G")], 90.0, 0.0, 12),
+            "plant" => (
+                "X",
+                &[('X', "F+[[X]-X]-F[-FX]+X"), ('F', "FF")],
+                25.0,
+                90.0,
+                6,
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            axiom: axiom.to_string(),
+            rules: rules.iter().map(|&(c, s)| (c, s.to_string())).collect(),
+            angle,
+            heading,
+            depth,
+        })
+    }
+
+    /// Sets the rule for `symbol`, replacing any earlier one.
+    pub fn set_rule(&mut self, symbol: char, replacement: String) {
+        self.rules.retain(|&(c, _)| c != symbol);
+        self.rules.push((symbol, replacement));
+    }
+
+    /// The axiom with the rules applied `depth` times.
+    pub fn expand(&self) -> Result<String, String> {
+        let mut current = self.axiom.clone();
+        let rule = |c| self.rules.iter().find(|&&(symbol, _)| symbol == c);
+        for _ in 0..self.depth {
+            let length: usize = current
+                .chars()
+                .map(|c| rule(c).map_or(c.len_utf8(), |(_, replacement)| replacement.len()))
+                .sum();
+            if length > MAX_SYMBOLS {
+                return Err(format!(
+                    "the L-system grows beyond {MAX_SYMBOLS} symbols; lower the depth"
+                ));
+            }
+            let mut next = String::with_capacity(length);
+            for c in current.chars() {
+                match rule(c) {
+                    Some((_, replacement)) => next.push_str(replacement),
+                    None => next.push(c),
+                }
+            }
+            current = next;
+        }
+        Ok(current)
+    }
+
+    /// The lines the turtle draws, in order, with steps of length 1 and the
+    /// `y` axis pointing up.
+    pub fn lines(&self) -> Result<Vec<(Complex, Complex)>, String> {
+        let turn = Complex::from_polar(1.0, self.angle.to_radians());
+        let mut position = Complex::ZERO;
+        let mut heading = Complex::from_polar(1.0, self.heading.to_radians());
+        let symbols = self.expand()?;
+        let steps = symbols.chars().filter(|&c| c == 'F' || c == 'G').count();
+        if steps > MAX_LINES {
+            return Err(format!(
+                "the L-system draws more than {MAX_LINES} lines; lower the depth"
+            ));
+        }
+        let mut stack = Vec::new();
+        let mut lines = Vec::with_capacity(steps);
+        for c in symbols.chars() {
+            match c {
+                'F' | 'G' => {
+                    lines.push((position, position + heading));
+                    position += heading;
+                }
+                'f' | 'g' => position += heading,
+                '+' => heading *= turn,
+                '-' => heading /= turn,
+                '|' => heading = -heading,
+                '[' => stack.push((position, heading)),
+                ']' => {
+                    (position, heading) = stack
+                        .pop()
+                        .ok_or("the L-system closes a branch it never opened")?
+                }
+                _ => {}
+            }
+        }
+        Ok(lines)
+    }
+}
+
+/// A rule `SYMBOL=REPLACEMENT`, such as `F=F+F--F+F`.
+pub fn parse_rule(s: &str) -> Result<(char, String), String> {
+    let Some((symbol, replacement)) = s.split_once('=') else {
+        return Err("expected `SYMBOL=REPLACEMENT`".to_string());
+    };
+    let mut chars = symbol.trim().chars();
+    match (chars.next(), chars.next()) {
+        (Some(symbol), None) => Ok((symbol, replacement.trim().to_string())),
+        _ => Err(format!("'{}' is not a single symbol", symbol.trim())),
+    }
+}
+
+pub fn parse_lsystem(s: &str) -> Result<LSystem, String> {
+    LSystem::builtin(s.trim()).ok_or_else(|| {
+        format!(
+            "unknown L-system '{}'; expected one of {}",
+            s.trim(),
+            BUILTINS.join(", ")
+        )
+    })
+}
+
+/// Draws the lines of an [`LSystem`], scaled to fit the image and colored
+/// along the curve with the palette.
+pub struct Turtle {
+    pub lines: Vec<(Complex, Complex)>,
+    /// Width of the lines in pixels.
+    pub width: f64,
+    pub palette: Palette,
+}
+
+/// Part of the image left free around the curve.
+const MARGIN: f64 = 0.05;
+
+/// Colors a curve is split into for SVG output, each a single path.
+const BANDS: usize = 64;
+
+impl Turtle {
+    /// The lines in pixel coordinates, centered in the image.
+    fn fit(&self, width: usize, height: usize) -> Vec<(Complex, Complex)> {
+        let (mut min, mut max) = (
+            Complex::new(f64::INFINITY, f64::INFINITY),
+            Complex::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+        );
+        for &(a, b) in &self.lines {
+            for p in [a, b] {
+                min = Complex::new(min.re.min(p.re), min.im.min(p.im));
+                max = Complex::new(max.re.max(p.re), max.im.max(p.im));
+            }
+        }
+        let size = max - min;
+        let (w, h) = (width as f64, height as f64);
+        let scale = (1.0 - 2.0 * MARGIN) * (w / size.re.max(1e-9)).min(h / size.im.max(1e-9));
+        let middle = (min + max) / 2.0;
+        // Flips the y axis, which points down in the image.
+        let place = |p: Complex| {
+            let q = (p - middle) * scale;
+            Complex::new(w / 2.0 + q.re, h / 2.0 - q.im)
+        };
+        self.lines
+            .iter()
+            .map(|&(a, b)| (place(a), place(b)))
+            .collect()
+    }
+
+    /// The color of line `i`.
+    fn color(&self, i: usize) -> (u8, u8, u8) {
+        self.palette
+            .color(i as f64 / self.lines.len().saturating_sub(1).max(1) as f64)
+    }
+
+    /// The lines as an SVG document of the same size as the image.
+    pub fn to_svg(&self, width: usize, height: usize) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+             viewBox=\"0 0 {width} {height}\">\n\
+             <rect width=\"100%\" height=\"100%\" fill=\"black\"/>\n"
+        );
+        let lines = self.fit(width, height);
+        let band = lines.len().div_ceil(BANDS).max(1);
+        for (n, chunk) in lines.chunks(band).enumerate() {
+            let (r, g, b) = self.color(n * band + chunk.len() / 2);
+            let mut path = String::new();
+            let mut end = None;
+            for &(a, b) in chunk {
+                if end != Some(a) {
+                    write!(path, "M{:.2} {:.2}", a.re, a.im).unwrap();
+                }
+                write!(path, "L{:.2} {:.2}", b.re, b.im).unwrap();
+                end = Some(b);
+            }
+            writeln!(
+                svg,
+                "<path d=\"{path}\" fill=\"none\" stroke=\"#{r:02x}{g:02x}{b:02x}\" \
+                 stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+                self.width
+            )
+            .unwrap();
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Draws the line from `a` to `b` into `pixels`, covering every pixel by
+/// how much of it lies within half the width of the line.
+fn draw_line(
+    pixels: &mut [[f64; 3]],
+    width: usize,
+    a: Complex,
+    b: Complex,
+    line: f64,
+    color: [f64; 3],
+) {
+    let height = pixels.len() / width;
+    let reach = line / 2.0 + 0.5;
+    let (from_x, to_x) = (a.re.min(b.re) - reach, a.re.max(b.re) + reach);
+    let (from_y, to_y) = (a.im.min(b.im) - reach, a.im.max(b.im) + reach);
+    let ab = b - a;
+    let length_sqr = ab.norm_sqr();
+    for y in from_y.floor().max(0.0) as usize..(to_y.ceil().max(0.0) as usize).min(height) {
+        for x in from_x.floor().max(0.0) as usize..(to_x.ceil().max(0.0) as usize).min(width) {
+            let p = Complex::new(x as f64 + 0.5, y as f64 + 0.5);
+            let ap = p - a;
+            let t = if length_sqr > 0.0 {
+                ((ap.re * ab.re + ap.im * ab.im) / length_sqr).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let distance = (ap - ab * t).abs();
+            let coverage = (reach - distance).clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                let pixel = &mut pixels[y * width + x];
+                for (channel, value) in pixel.iter_mut().zip(color) {
+                    *channel += (value - *channel) * coverage;
+                }
+            }
+        }
+    }
+}
+
+impl Draw for Turtle {
+    fn draw(&self, settings: &Settings) -> Image {
+        let (width, height) = (settings.width, settings.height);
+        let mut pixels = vec![[0.0; 3]; width * height];
+        for (i, (from, to)) in self.fit(width, height).into_iter().enumerate() {
+            let (r, g, b) = self.color(i);
+            let color = [r, g, b].map(|v| v as f64);
+            draw_line(&mut pixels, width, from, to, self.width, color);
+        }
+        let mut img = Image::new(width, height);
+        for (out, pixel) in img.data.iter_mut().zip(pixels) {
+            let [r, g, b] = pixel.map(|v| v.round() as u8);
+            *out = Image::pack(r, g, b);
+        }
+        img
+    }
+
+    fn svg(&self, settings: &Settings) -> Option<String> {
+        Some(self.to_svg(settings.width, settings.height))
+    }
+}
+
+#[test]
+fn test_lsystem() {
+    let koch = LSystem::builtin("koch").unwrap();
+    for depth in 0..4 {
+        let koch = LSystem {
+            depth,
+            ..koch.clone()
+        };
+        let lines = koch.lines().unwrap();
+        assert!(lines.len() == 3 * 4usize.pow(depth as u32));
+        // The snowflake is closed.
+        assert!((lines[lines.len() - 1].1 - lines[0].0).abs() < 1e-9);
+    }
+
+    // The Hilbert curve visits every point of a grid once, one step apart.
+    let hilbert = LSystem::builtin("hilbert").unwrap();
+    let lines = LSystem {
+        depth: 3,
+        ..hilbert
+    }
+    .lines()
+    .unwrap();
+    assert!(lines.len() == 63);
+    let mut points: Vec<(i64, i64)> = lines
+        .iter()
+        .map(|(_, b)| (b.re.round() as i64, b.im.round() as i64))
+        .collect();
+    points.sort();
+    points.dedup();
+    assert!(points.len() == 63, "The Hilbert curve crossed itself.");
+
+    let mut plant = LSystem::builtin("plant").unwrap();
+    plant.depth = 1;
+    assert!(plant.expand().unwrap() == "F+[[X]-X]-F[-FX]+X");
+    plant.depth = 2;
+    assert!(plant
+        .expand()
+        .unwrap()
+        .starts_with("FF+[[F+[[X]-X]-F[-FX]+X]-"));
+    plant.set_rule('F', "F".to_string());
+    assert!(plant.rules.len() == 2 && plant.rules[1] == ('F', "F".to_string()));
+    plant.axiom = "F]".to_string();
+    assert!(plant.lines().is_err(), "An unopened branch was accepted.");
+    plant.depth = 100;
+    plant.axiom = "FF".to_string();
+    plant.set_rule('F', "F".repeat(5000));
+    assert!(
+        plant.expand().is_err(),
+        "An endless expansion was accepted."
+    );
+    // Two million steps fit in the string but are too many to draw.
+    plant.depth = 10;
+    plant.set_rule('F', "FFFF".to_string());
+    assert!(plant.expand().is_ok());
+    assert!(plant.lines().is_err(), "Too many lines were accepted.");
+
+    assert!(parse_rule("F = F+F") == Ok(('F', "F+F".to_string())));
+    assert!(parse_rule("FF=F").is_err());
+    assert!(parse_lsystem("tree").is_err());
+}
+
+#[test]
+fn test_turtle() {
+    let turtle = Turtle {
+        lines: LSystem::builtin("dragon").unwrap().lines().unwrap(),
+        width: 1.5,
+        palette: Palette::builtin("gray").unwrap(),
+    };
+    let settings = crate::density::test_settings(1);
+    let img = turtle.draw(&settings);
+    assert!(img.data.contains(&0xffffff));
+    // Edges of the lines are blended with the background.
+    assert!(img
+        .data
+        .iter()
+        .any(|&pixel| pixel & 0xff > 0 && pixel & 0xff < 100));
+
+    let svg = turtle.to_svg(32, 32);
+    assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+    assert!(svg.matches("<path").count() == BANDS);
+    assert!(svg.contains("stroke-width=\"1.5\""));
+}
@@ -0,0 +1,123 @@
+use crate::palette::Palette;
+use crate::shader::Shader;
+use crate::Complex;
+
+/// The order of the rates `a` and `b`, such as `AABAB`; `true` stands for
+/// `B`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sequence(pub Vec<bool>);
+
+impl Sequence {
+    /// The rate for step `n`.
+    fn rate(&self, n: usize, a: f64, b: f64) -> f64 {
+        if self.0[n % self.0.len()] {
+            b
+        } else {
+            a
+        }
+    }
+}
+
+pub fn parse_sequence(s: &str) -> Result<Sequence, String> {
+    let sequence = s
+        .trim()
+        .chars()
+        .map(|c| match c.to_ascii_uppercase() {
+            'A' => Ok(false),
+            'B' => Ok(true),
+            c => Err(format!("'{c}' is neither A nor B")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if sequence.is_empty() {
+        return Err("the sequence is empty".to_string());
+    }
+    Ok(Sequence(sequence))
+}
+
+/// The Markus–Lyapunov fractal: the logistic map `x = r x (1 - x)` with
+/// `r` switching between `a` and `b`, the coordinates of the point, in the
+/// order of `sequence`. Points are colored by the Lyapunov exponent of the
+/// orbit, with one palette for stable orbits (negative exponent) and
+/// another for chaotic ones (positive exponent). Both run from the edge of
+/// chaos at zero towards the extremes.
+pub struct Lyapunov {
+    pub sequence: Sequence,
+    /// Length of the orbit. The first quarter lets it settle and is left
+    /// out of the exponent.
+    pub iterations: usize,
+    pub stable: Palette,
+    pub chaotic: Palette,
+}
+
+impl Lyapunov {
+    /// The Lyapunov exponent of the orbit for `a` and `b`, the average of
+    /// `ln|r (1 - 2x)|`.
+    pub fn exponent(&self, a: f64, b: f64) -> f64 {
+        let warmup = self.iterations / 4;
+        let r = |n| self.sequence.rate(n, a, b);
+
+        let mut x = 0.5;
+        for n in 0..warmup {
+            x = r(n) * x * (1.0 - x);
+        }
+        let count = (self.iterations - warmup).max(1);
+        let mut sum = 0.0;
+        for n in warmup..warmup + count {
+            let r = r(n);
+            sum += (r * (1.0 - 2.0 * x)).abs().ln();
+            x = r * x * (1.0 - x);
+        }
+        sum / count as f64
+    }
+}
+
+impl Shader for Lyapunov {
+    fn shade(&self, point: Complex) -> (u8, u8, u8) {
+        let exponent = self.exponent(point.re, point.im);
+        if exponent.is_nan() {
+            return (0, 0, 0);
+        }
+        let t = 1.0 - (-exponent.abs()).exp();
+        if exponent <= 0.0 {
+            self.stable.color(t)
+        } else {
+            self.chaotic.color(t)
+        }
+    }
+}
+
+#[test]
+fn test_sequence() {
+    assert!(parse_sequence("AaBab") == Ok(Sequence(vec![false, false, true, false, true])));
+    assert!(parse_sequence("ABC").is_err(), "A C was accepted.");
+    assert!(
+        parse_sequence(" ").is_err(),
+        "An empty sequence was accepted."
+    );
+}
+
+#[test]
+fn test_lyapunov() {
+    let lyapunov = |sequence: &str| Lyapunov {
+        sequence: parse_sequence(sequence).unwrap(),
+        iterations: 2000,
+        stable: Palette::builtin("gray").unwrap(),
+        chaotic: Palette::builtin("fire").unwrap(),
+    };
+
+    // With a single rate the exponent is that of the logistic map: ln|2 - r|
+    // on the stable fixed point for 1 < r < 3, positive where it is chaotic.
+    let single = lyapunov("A");
+    assert!((single.exponent(2.5, 0.0) - 0.5f64.ln()).abs() < 1e-6);
+    assert!(single.exponent(3.2, 0.0) < 0.0, "A 2-cycle is stable.");
+    assert!(single.exponent(3.9, 0.0) > 0.0);
+
+    // Swapping a and b only shifts the sequence AB.
+    let ab = lyapunov("AB");
+    assert!((ab.exponent(3.4, 3.9) - ab.exponent(3.9, 3.4)).abs() < 0.01);
+
+    // Superstable orbits hit x = 1/2 and have an exponent of minus infinity.
+    assert!(single.exponent(2.0, 0.0) == f64::NEG_INFINITY);
+    assert!(single.shade(Complex::new(2.0, 0.0)) == (255, 255, 255));
+    assert!(single.shade(Complex::new(3.9, 0.0)) != single.shade(Complex::new(2.5, 0.0)));
+}
@@ -1,11 +1,33 @@
-use std::{
-    fmt::Debug,
-    ops::{self, Add, Div, Mul, Sub},
-};
+mod attractor;
+mod average;
+mod cli;
+mod complex;
+mod density;
+mod flame;
+mod formula;
+mod fractal;
+mod ifs;
+mod lsystem;
+mod lyapunov;
+mod newton;
+mod palette;
+mod random;
+mod shader;
+mod trap;
+
+use std::ops::{Add, Div, Mul, Sub};
 
 use std::fs::File;
 use std::io::BufWriter;
 use std::path::Path;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use clap::{Parser, ValueEnum};
+use cli::{Cli, Command, FractalKind, View};
+use complex::Complex;
+use shader::Shader;
 
 struct Image {
     width: usize,
@@ -20,10 +42,11 @@ impl Image {
         Self {
             width,
             height,
-            data
+            data,
         }
     }
 
+    #[allow(dead_code)]
     fn set_color(&mut self, x: usize, y: usize, r: u8, g: u8, b: u8) {
         assert!(x < self.width, "Tried setting a color in an invalid pixel.");
         assert!(
@@ -31,18 +54,27 @@ impl Image {
             "Tried setting a color in an invalid pixel."
         );
 
-        self.data[y * self.width + x] = (r as i32) << 16 | (g as i32) << 8 | (b as i32);
+        self.data[y * self.width + x] = Self::pack(r, g, b);
+    }
+
+    fn pack(r: u8, g: u8, b: u8) -> i32 {
+        (r as i32) << 16 | (g as i32) << 8 | (b as i32)
     }
 
-    fn save(&self, filename: &str) {
+    /// The pixel rows, top to bottom, for filling in parallel.
+    fn rows_mut(&mut self) -> std::slice::ChunksMut<'_, i32> {
+        self.data.chunks_mut(self.width)
+    }
+
+    fn save(&self, filename: &str) -> Result<(), png::EncodingError> {
         let filename = if filename.ends_with(".png") {
             filename.to_string()
         } else {
             format!("{filename}.png")
         };
         let path = Path::new(filename.as_str());
-        let file = File::create(path).unwrap();
-        let ref mut w = BufWriter::new(file);
+        let file = File::create(path)?;
+        let w = &mut BufWriter::new(file);
 
         let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
         encoder.set_color(png::ColorType::Rgb);
@@ -56,119 +88,113 @@ impl Image {
             (0.15000, 0.06000),
         );
         encoder.set_source_chromaticities(source_chromaticities);
-        let mut writer = encoder.write_header().unwrap();
+        let mut writer = encoder.write_header()?;
+
+        writer.write_image_data(self.rgb().as_slice()) // Save
+    }
 
-        let split_data = self
-            .data
+    /// The pixels as consecutive red, green and blue bytes.
+    fn rgb(&self) -> Vec<u8> {
+        self.data
             .iter()
             .flat_map(|&i32| [(i32 >> 16) as u8, (i32 >> 8) as u8, i32 as u8])
-            .collect::<Vec<_>>();
-        writer.write_image_data(&split_data.as_slice()).unwrap(); // Save
+            .collect()
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
-struct Complex {
-    re: f64,
-    im: f64,
+struct Settings {
+    view: View,
+    width: usize,
+    height: usize,
+    threads: usize,
 }
 
-impl Complex {
-    fn new(re: f64, im: f64) -> Self {
-        Self { re, im }
-    }
+/// Draws a whole image at once, for renderers whose pixels depend on each
+/// other, such as orbit densities.
+trait Draw: Sync {
+    fn draw(&self, settings: &Settings) -> Image;
 
-    fn abs(&self) -> f64 {
-        self.re.powi(2).add(self.im.powi(2)).sqrt()
+    /// The same drawing as an SVG document, for drawings made of lines.
+    fn svg(&self, _settings: &Settings) -> Option<String> {
+        None
     }
 }
 
-impl ops::Add<Complex> for Complex {
-    type Output = Complex;
-
-    fn add(self, rhs: Complex) -> Self::Output {
-        Self::Output::new(self.re + rhs.re, self.im + rhs.im)
-    }
+/// What fills the image.
+enum Renderer {
+    /// Shades every pixel on its own.
+    Pixels(Box<dyn Shader>),
+    Drawing(Box<dyn Draw>),
 }
 
-impl ops::Sub<Complex> for Complex {
-    type Output = Complex;
-
-    fn sub(self, rhs: Complex) -> Self::Output {
-        Self::Output::new(self.re - rhs.re, self.im - rhs.im)
-    }
-}
+fn generate(
+    settings: &Settings,
+    renderer: &Renderer,
+    filename: &str,
+) -> Result<(), png::EncodingError> {
+    let img = match renderer {
+        Renderer::Pixels(shader) => render(settings, shader.as_ref()),
+        Renderer::Drawing(drawing) => drawing.draw(settings),
+    };
 
-impl ops::Mul<Complex> for Complex {
-    type Output = Complex;
+    print!("\rSaving to '{filename}.png'...");
+    img.save(filename)?;
+    println!("\rSaved to '{filename}.png'.   ");
 
-    fn mul(self, rhs: Complex) -> Self::Output {
-        let a = self.re;
-        let b = self.im;
-        let c = rhs.re;
-        let d = rhs.im;
-        Self::Output::new(a * c - b * d, a * d + b * c)
+    if let Renderer::Drawing(drawing) = renderer {
+        if let Some(svg) = drawing.svg(settings) {
+            let filename = filename.strip_suffix(".png").unwrap_or(filename);
+            std::fs::write(format!("{filename}.svg"), svg)?;
+            println!("Saved to '{filename}.svg'.");
+        }
     }
+    Ok(())
 }
 
-impl Debug for Complex {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("").field(&self.re).field(&self.im).finish()
+/// Renders the image with rows dealt out round-robin to `settings.threads`
+/// workers. Every pixel is computed independently, so the result does not
+/// depend on the number of threads.
+fn render(settings: &Settings, shader: &dyn Shader) -> Image {
+    let mut img: Image = Image::new(settings.width, settings.height);
+    let threads = settings.threads.clamp(1, settings.height);
+
+    let mut work: Vec<Vec<(usize, &mut [i32])>> = (0..threads).map(|_| Vec::new()).collect();
+    for (y, row) in img.rows_mut().enumerate() {
+        work[y % threads].push((y, row));
     }
-}
 
-fn generate(
-    from_x: f64,
-    to_x: f64,
-    from_y: f64,
-    to_y: f64,
-    width: usize,
-    height: usize,
-    filename: &str,
-) {
-    const MAX_ITERATIONS: usize = 255 + 255 + 255;
+    let done = AtomicUsize::new(0);
+    print!("0%");
+    thread::scope(|scope| {
+        for rows in work {
+            let done = &done;
+            scope.spawn(move || {
+                for (y, row) in rows {
+                    render_row(settings, shader, y, row);
+                    let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                    print!("\r{}%", 100 * done / settings.height);
+                }
+            });
+        }
+    });
 
-    let mut img: Image = Image::new(width, height);
+    img
+}
 
-    print!("0%");
-    for x in 0..width {
-        for y in 0..height {
-            let mut iterations = 0;
-
-            let a = map(x as f64, 0.0, width as f64, from_x, to_x);
-            let b = map(y as f64, 0.0, height as f64, from_y, to_y);
-            let c = Complex::new(-0.4f64, 0.5868f64);
-            // let mut z = Complex::new(0f64, 0f64);
-            let mut z = Complex::new(a, b);
-
-            while z.abs() <= 2_f64 && iterations < MAX_ITERATIONS {
-                z = z * z + c;
-                iterations += 1;
-            }
+fn render_row(settings: &Settings, shader: &dyn Shader, y: usize, row: &mut [i32]) {
+    let Settings {
+        view,
+        width,
+        height,
+        ..
+    } = *settings;
+    let b = map(y as f64, 0.0, height as f64, view.from_y, view.to_y);
 
-            let mut r = 0u8;
-            let mut g = 0u8;
-            let mut b = 0u8;
-            if iterations <= 255 {
-                r = iterations as u8;
-            } else if iterations <= 255 + 255 {
-                r = 255;
-                g = (iterations - 255) as u8;
-            } else {
-                r = 255;
-                g = 255;
-                b = (iterations - 255 - 255) as u8;
-            }
-            // let r = map((iterations) as f64, 0f64, 255 as f64, 0f64, 255f64) as u8;
-            // let g = map((iterations) as f64, 0f64, 255 as f64, 0f64, 255f64) as u8;
-            img.set_color(x, y, r, g, b);
-        }
-        print!("\r{}%", (100.0 * x as f64) as usize / width);
+    for (x, pixel) in row.iter_mut().enumerate() {
+        let a = map(x as f64, 0.0, width as f64, view.from_x, view.to_x);
+        let (r, g, b) = shader.shade(Complex::new(a, b));
+        *pixel = Image::pack(r, g, b);
     }
-    
-    print!("\rSaving to '{filename}.png'...");
-    img.save(filename);
-    println!("\rSaved to '{filename}.png'.   ");
 }
 
 // https://stackoverflow.com/a/5732390
@@ -177,8 +203,53 @@ fn map(v: f64, from_a: f64, from_b: f64, to_a: f64, to_b: f64) -> f64 {
     to_a.add(to_b.sub(to_a).div(from_b.sub(from_a)).mul(v.sub(from_a)))
 }
 
-fn main() {
-    generate(-2f64, 2f64, -2f64, 2f64, 1 << 10, 1 << 10, "output");
+fn main() -> ExitCode {
+    match Cli::parse().command {
+        Command::Render(args) => {
+            let (width, height) = args.size;
+            let renderer = match args.renderer() {
+                Ok(renderer) => renderer,
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let settings = Settings {
+                view: args.view(),
+                width,
+                height,
+                threads: args.threads(),
+            };
+            let result = generate(&settings, &renderer, &args.output);
+            if let Err(err) = result {
+                eprintln!("\nerror: could not save '{}': {err}", args.output);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::List => {
+            println!("Fractals:");
+            for fractal in FractalKind::value_variants() {
+                println!("  {}", fractal.to_possible_value().unwrap().get_name());
+            }
+            println!("Palettes:");
+            for name in palette::BUILTINS {
+                println!("  {name}");
+            }
+            println!("Iterated function systems:");
+            for name in ifs::BUILTINS {
+                println!("  {name}");
+            }
+            println!("L-systems:");
+            for name in lsystem::BUILTINS {
+                println!("  {name}");
+            }
+            println!("Flames:");
+            for name in flame::BUILTINS {
+                println!("  {name}");
+            }
+        }
+    }
+    ExitCode::SUCCESS
 }
 
 #[test]
@@ -200,16 +271,91 @@ fn test_image() {
 }
 
 #[test]
-fn test_complex() {
-    let a: f64 = 1.0;
-    let b: f64 = 2.0;
-    let c: f64 = -1.0;
-    let d: f64 = 3.0;
-
-    let z1: Complex = Complex { re: a, im: b };
-    let z2: Complex = Complex::new(c, d);
-
-    assert!(z1 + z2 == Complex::new(a + c, b + d));
-    assert!(z1 - z2 == Complex::new(a - c, b - d));
-    assert!(z1 * z2 == Complex::new(a * c - b * d, a * d + b * c));
+fn test_render_threads() {
+    use palette::Palette;
+    use shader::EscapeTime;
+
+    let shader = EscapeTime {
+        fractal: Box::new(fractal::Mandelbrot),
+        palette: Palette::builtin("fire").unwrap(),
+        max_iterations: 200,
+        smooth: false,
+        bailout: 2.0,
+        coloring: shader::Coloring::Iterations,
+    };
+    let mut settings = Settings {
+        view: View {
+            from_x: -2.0,
+            to_x: 1.0,
+            from_y: -1.5,
+            to_y: 1.5,
+        },
+        width: 60,
+        height: 45,
+        threads: 1,
+    };
+    let single = render(&settings, &shader);
+    settings.threads = 7;
+    let multi = render(&settings, &shader);
+
+    assert!(
+        single.data == multi.data,
+        "The multithreaded render differs from the single-threaded one."
+    );
+}
+
+/// Compares small renders of the Mandelbrot-like variants with the reference
+/// images in `tests/fixtures`. Set `UPDATE_FIXTURES=1` to rewrite them after
+/// an intended change.
+#[test]
+fn test_variant_fixtures() {
+    use fractal::{Variant, Variation};
+    use palette::Palette;
+    use shader::EscapeTime;
+
+    let variants = [
+        (Variant::BurningShip, "burning-ship"),
+        (Variant::Tricorn, "tricorn"),
+        (Variant::Celtic, "celtic"),
+        (Variant::Buffalo, "buffalo"),
+        (Variant::Perpendicular, "perpendicular"),
+        (Variant::Heart, "heart"),
+    ];
+    let update = std::env::var_os("UPDATE_FIXTURES").is_some();
+
+    for (variant, name) in variants {
+        for (julia, suffix) in [(None, ""), (Some(Complex::new(-0.4, 0.6)), "-julia")] {
+            let shader = EscapeTime {
+                fractal: Box::new(Variation { variant, julia }),
+                palette: Palette::builtin("gray").unwrap(),
+                max_iterations: 100,
+                smooth: false,
+                bailout: 2.0,
+                coloring: shader::Coloring::Iterations,
+            };
+            let settings = Settings {
+                view: View {
+                    from_x: -2.0,
+                    to_x: 2.0,
+                    from_y: -2.0,
+                    to_y: 2.0,
+                },
+                width: 64,
+                height: 64,
+                threads: 2,
+            };
+            let img = render(&settings, &shader);
+            let path = format!("tests/fixtures/{name}{suffix}.png");
+
+            if update {
+                img.save(&path).unwrap();
+                continue;
+            }
+            let decoder = png::Decoder::new(File::open(&path).unwrap());
+            let mut reader = decoder.read_info().unwrap();
+            let mut expected = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut expected).unwrap();
+            assert!(img.rgb() == expected, "The render differs from {path}.");
+        }
+    }
 }
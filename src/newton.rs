@@ -0,0 +1,417 @@
+use crate::palette::Palette;
+use crate::shader::Shader;
+use crate::Complex;
+
+/// A polynomial with complex coefficients.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polynomial {
+    /// Coefficients from the constant term up.
+    coefficients: Vec<Complex>,
+}
+
+impl Polynomial {
+    /// From coefficients given highest degree first, so `[1, 0, 0, -1]` is
+    /// `z^3 - 1`. Leading zeros are dropped.
+    pub fn from_coefficients(coefficients: &[Complex]) -> Self {
+        let mut coefficients: Vec<Complex> = coefficients
+            .iter()
+            .copied()
+            .skip_while(|&c| c == Complex::ZERO)
+            .collect();
+        coefficients.reverse();
+        Self { coefficients }
+    }
+
+    /// The monic polynomial with the given roots.
+    pub fn from_roots(roots: &[Complex]) -> Self {
+        let mut coefficients = vec![Complex::ONE];
+        for &root in roots {
+            // Multiply by (z - root).
+            let mut next = vec![Complex::ZERO; coefficients.len() + 1];
+            for (i, &c) in coefficients.iter().enumerate() {
+                next[i + 1] += c;
+                next[i] -= c * root;
+            }
+            coefficients = next;
+        }
+        Self { coefficients }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    pub fn eval(&self, z: Complex) -> Complex {
+        self.derivatives(z)[0]
+    }
+
+    /// `p(z)`, `p'(z)` and `p''(z)`, by Horner's scheme.
+    pub fn derivatives(&self, z: Complex) -> [Complex; 3] {
+        let mut p = Complex::ZERO;
+        let mut dp = Complex::ZERO;
+        let mut ddp = Complex::ZERO;
+        for &c in self.coefficients.iter().rev() {
+            ddp = ddp * z + dp * 2.0;
+            dp = dp * z + p;
+            p = p * z + c;
+        }
+        [p, dp, ddp]
+    }
+
+    /// All roots, found with the Durand–Kerner method.
+    pub fn roots(&self) -> Vec<Complex> {
+        let degree = self.degree();
+        if degree == 0 {
+            return Vec::new();
+        }
+        let lead = self.coefficients[degree];
+        let monic = Self {
+            coefficients: self.coefficients.iter().map(|&c| c / lead).collect(),
+        };
+
+        let seed = Complex::new(0.4, 0.9);
+        let mut roots: Vec<Complex> = (0..degree).map(|k| seed.powi(k as i32)).collect();
+        for _ in 0..1000 {
+            let mut change: f64 = 0.0;
+            for i in 0..degree {
+                let mut denominator = Complex::ONE;
+                for (j, &other) in roots.iter().enumerate() {
+                    if i != j {
+                        denominator *= roots[i] - other;
+                    }
+                }
+                let delta = monic.eval(roots[i]) / denominator;
+                roots[i] -= delta;
+                change = change.max(delta.abs());
+            }
+            if change < 1e-14 {
+                break;
+            }
+        }
+        roots
+    }
+}
+
+/// A root-finding iteration `z = z - step(z)`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Method {
+    /// `p / p'`.
+    Newton,
+    /// `2 p p' / (2 p'^2 - p p'')`, which converges cubically.
+    Halley,
+    /// `p p' / (p'^2 - p p'')`, which stays quadratic at multiple roots.
+    Schroder,
+}
+
+impl Method {
+    /// The step at a point where the polynomial and its first two
+    /// derivatives are `[p, dp, ddp]`, or `None` where it is undefined.
+    pub fn step(self, [p, dp, ddp]: [Complex; 3]) -> Option<Complex> {
+        let (numerator, denominator) = match self {
+            Method::Newton => (p, dp),
+            Method::Halley => (p * dp * 2.0, dp * dp * 2.0 - p * ddp),
+            Method::Schroder => (p * dp, dp * dp - p * ddp),
+        };
+        (denominator != Complex::ZERO).then(|| numerator / denominator)
+    }
+
+    /// Order of convergence near a simple root.
+    pub fn order(self) -> f64 {
+        match self {
+            Method::Newton | Method::Schroder => 2.0,
+            Method::Halley => 3.0,
+        }
+    }
+}
+
+/// Where a root-finding orbit ended up.
+#[derive(Debug, PartialEq)]
+pub struct Convergence {
+    pub z: Complex,
+    pub iterations: usize,
+    /// Whether the last step was shorter than the tolerance.
+    pub converged: bool,
+    /// Length of the last step.
+    pub distance: f64,
+}
+
+impl Convergence {
+    /// The iteration count with a fractional part, continuous across the
+    /// bands where the count jumps, for a method of the given order.
+    pub fn smooth(&self, tolerance: f64, order: f64) -> f64 {
+        if !self.converged || self.distance <= 0.0 {
+            return self.iterations as f64;
+        }
+        let overshoot = self.distance.ln() / tolerance.ln();
+        self.iterations as f64 - overshoot.ln() / order.ln()
+    }
+}
+
+/// A root-finding method on a polynomial, such as Newton's method
+/// `z = z - p(z) / p'(z)`.
+pub struct Newton {
+    pub polynomial: Polynomial,
+    pub roots: Vec<Complex>,
+    /// The iteration stops once a step is shorter than this.
+    pub tolerance: f64,
+    pub method: Method,
+    /// Factor `R` applied to every step; 1 is the plain method.
+    pub relaxation: Complex,
+}
+
+impl Newton {
+    /// Newton's method, finding the roots of `polynomial` numerically.
+    pub fn new(polynomial: Polynomial, tolerance: f64) -> Self {
+        Self {
+            roots: polynomial.roots(),
+            polynomial,
+            tolerance,
+            method: Method::Newton,
+            relaxation: Complex::ONE,
+        }
+    }
+
+    pub fn converge(&self, z: Complex, max_iterations: usize) -> Convergence {
+        self.orbit(z, Complex::ZERO, max_iterations)
+    }
+
+    /// Iterates `z = z - R * step(z) + c` until it settles.
+    pub fn orbit(&self, mut z: Complex, c: Complex, max_iterations: usize) -> Convergence {
+        let tolerance_sqr = self.tolerance * self.tolerance;
+        let mut distance = f64::INFINITY;
+        for iterations in 1..=max_iterations {
+            let Some(step) = self.method.step(self.polynomial.derivatives(z)) else {
+                break;
+            };
+            let next = z - self.relaxation * step + c;
+            let distance_sqr = (next - z).norm_sqr();
+            z = next;
+            distance = distance_sqr.sqrt();
+            if distance_sqr < tolerance_sqr {
+                return Convergence {
+                    z,
+                    iterations,
+                    converged: true,
+                    distance,
+                };
+            }
+        }
+        Convergence {
+            z,
+            iterations: max_iterations,
+            converged: false,
+            distance,
+        }
+    }
+
+    /// The root `z` has settled on. Multiple roots converge slowly, so the
+    /// point may still be some way off.
+    pub fn nearest_root(&self, z: Complex) -> Option<usize> {
+        let radius = self.tolerance.sqrt().max(1e-6);
+        self.roots
+            .iter()
+            .map(|&root| (z - root).abs())
+            .enumerate()
+            .filter(|&(_, distance)| distance < radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+}
+
+/// Colors every point by the root it converges to, taking evenly spaced
+/// colors from the palette and darkening them the longer convergence takes.
+/// Points that do not reach a root are black.
+pub struct NewtonShader {
+    pub newton: Newton,
+    pub palette: Palette,
+    pub max_iterations: usize,
+}
+
+impl Shader for NewtonShader {
+    fn shade(&self, point: Complex) -> (u8, u8, u8) {
+        let convergence = self.newton.converge(point, self.max_iterations);
+        let root = match convergence.converged {
+            true => self.newton.nearest_root(convergence.z),
+            false => None,
+        };
+        let Some(root) = root else {
+            return (0, 0, 0);
+        };
+
+        let t = (root as f64 + 0.5) / self.newton.roots.len() as f64;
+        let (r, g, b) = self.palette.color(t);
+        let shade = 1.0
+            - (1.0 + convergence.iterations as f64).ln() / (2.0 + self.max_iterations as f64).ln();
+        let scale = |v: u8| (v as f64 * shade).round() as u8;
+        (scale(r), scale(g), scale(b))
+    }
+}
+
+/// The Nova fractal `z = z - R * p(z) / p'(z) + c`, whose orbits settle on
+/// points that are generally not roots of `p`.
+pub struct Nova {
+    pub newton: Newton,
+    /// `c` of the Julia variant, which starts from the pixel. Otherwise `c`
+    /// is the pixel and every orbit starts from the root with the largest
+    /// real part (1 for `z^3 - 1`), the customary start of the Nova orbit.
+    pub julia: Option<Complex>,
+}
+
+impl Nova {
+    pub fn converge(&self, pixel: Complex, max_iterations: usize) -> Convergence {
+        let (z, c) = match self.julia {
+            Some(c) => (pixel, c),
+            None => {
+                let start = self
+                    .newton
+                    .roots
+                    .iter()
+                    .copied()
+                    .max_by(|a, b| a.re.total_cmp(&b.re));
+                (start.unwrap_or(Complex::ZERO), pixel)
+            }
+        };
+        self.newton.orbit(z, c, max_iterations)
+    }
+}
+
+/// Colors a [`Nova`] fractal by how many iterations its orbits take to
+/// settle.
+pub struct NovaShader {
+    pub nova: Nova,
+    pub palette: Palette,
+    pub max_iterations: usize,
+    pub smooth: bool,
+}
+
+impl Shader for NovaShader {
+    fn shade(&self, point: Complex) -> (u8, u8, u8) {
+        let convergence = self.nova.converge(point, self.max_iterations);
+        let newton = &self.nova.newton;
+        let value = if self.smooth {
+            convergence.smooth(newton.tolerance, newton.method.order())
+        } else {
+            convergence.iterations as f64
+        };
+        self.palette.color(value / self.max_iterations as f64)
+    }
+}
+
+#[test]
+fn test_polynomial() {
+    let roots = [Complex::ONE, Complex::new(-2.0, 0.5), Complex::I];
+    let p = Polynomial::from_roots(&roots);
+    assert!(p.degree() == 3);
+    for root in roots {
+        assert!(p.eval(root).abs() < 1e-12, "{root} is not a root.");
+    }
+
+    // z^3 - 2z + 1 and its derivatives at 2.
+    let cubic =
+        Polynomial::from_coefficients(&[0.0, 1.0, 0.0, -2.0, 1.0].map(|c| Complex::new(c, 0.0)));
+    assert!(cubic.degree() == 3, "The leading zero was kept.");
+    assert!(
+        cubic.derivatives(Complex::new(2.0, 0.0))
+            == [5.0, 10.0, 12.0].map(|c| Complex::new(c, 0.0))
+    );
+
+    let mut found = p.roots();
+    assert!(found.len() == 3);
+    for root in roots {
+        let (i, _) = found
+            .iter()
+            .enumerate()
+            .min_by(|a, b| (*a.1 - root).abs().total_cmp(&(*b.1 - root).abs()))
+            .unwrap();
+        assert!(
+            (found.remove(i) - root).abs() < 1e-9,
+            "Missed the root {root}."
+        );
+    }
+}
+
+#[test]
+fn test_newton() {
+    let p =
+        Polynomial::from_coefficients(&[Complex::ONE, Complex::ZERO, Complex::ZERO, -Complex::ONE]);
+    let newton = Newton::new(p, 1e-9);
+
+    for (i, &root) in newton.roots.iter().enumerate() {
+        let convergence = newton.converge(root * 1.1, 100);
+        assert!(
+            newton.nearest_root(convergence.z) == Some(i),
+            "{root} did not attract its neighbourhood."
+        );
+        assert!(convergence.iterations < 10);
+    }
+
+    // The derivative vanishes at zero.
+    assert!(!newton.converge(Complex::ZERO, 100).converged);
+}
+
+#[test]
+fn test_methods() {
+    // A double root at 1 slows Newton's method down but not Schröder's.
+    let p = Polynomial::from_roots(&[Complex::ONE, Complex::ONE, Complex::new(-2.0, 0.0)]);
+    let start = Complex::new(1.5, 0.2);
+    let mut newton = Newton::new(p, 1e-9);
+    let mut iterations = |method| {
+        newton.method = method;
+        let convergence = newton.converge(start, 200);
+        assert!(convergence.converged);
+        assert!(
+            (convergence.z - Complex::ONE).abs() < 1e-6,
+            "{method:?} missed the root."
+        );
+        convergence.iterations
+    };
+    let plain = iterations(Method::Newton);
+    assert!(iterations(Method::Halley) < plain);
+    assert!(iterations(Method::Schroder) < plain);
+
+    // Smoothing is continuous where the iteration count jumps.
+    let cubic =
+        Polynomial::from_coefficients(&[Complex::ONE, Complex::ZERO, Complex::ZERO, -Complex::ONE]);
+    let newton = Newton::new(cubic, 1e-6);
+    let smooth = |x| {
+        let convergence = newton.converge(Complex::new(x, 0.0), 100);
+        (convergence.iterations, convergence.smooth(1e-6, 2.0))
+    };
+    let (mut low, mut high) = (1.1, 10.0);
+    assert!(smooth(low).0 != smooth(high).0);
+    while high - low > 1e-9 {
+        let middle = (low + high) / 2.0;
+        if smooth(middle).0 == smooth(low).0 {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let (a, b) = (smooth(low).1, smooth(high).1);
+    assert!((a - b).abs() < 0.05, "{a} and {b} are not continuous.");
+}
+
+#[test]
+fn test_nova() {
+    let cubic =
+        Polynomial::from_coefficients(&[Complex::ONE, Complex::ZERO, Complex::ZERO, -Complex::ONE]);
+    let nova = Nova {
+        newton: Newton::new(cubic, 1e-9),
+        julia: None,
+    };
+    // With c = 0 the orbit starts on the root 1 and stays there.
+    let convergence = nova.converge(Complex::ZERO, 100);
+    assert!(convergence.converged && convergence.iterations == 1);
+    // Otherwise it settles on a fixed point of the iteration, not a root.
+    let c = Complex::new(0.1, 0.0);
+    let convergence = nova.converge(c, 100);
+    assert!(convergence.converged);
+    let z = convergence.z;
+    let drift = c - nova
+        .newton
+        .method
+        .step(nova.newton.polynomial.derivatives(z))
+        .unwrap();
+    assert!(drift.abs() < 1e-6, "The orbit did not settle.");
+    assert!(nova.newton.nearest_root(z).is_none());
+}
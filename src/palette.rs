@@ -0,0 +1,400 @@
+mod formats;
+
+use clap::ValueEnum;
+
+/// Color space in which neighbouring stops are blended.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Interpolation {
+    Rgb,
+    /// Hue, saturation and value; hue takes the shorter way around.
+    Hsv,
+    /// Perceptually uniform; avoids the muddy midpoints of RGB blends.
+    Oklab,
+}
+
+/// What happens to values outside the gradient.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Mode {
+    /// The gradient wraps around, blending the last stop into the first.
+    Cyclic,
+    /// Values are held at the first or last stop.
+    Clamped,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stop {
+    /// Position along the gradient, between 0 and 1.
+    pub position: f64,
+    pub color: [u8; 3],
+}
+
+/// A multi-stop gradient mapping a coloring value to a color.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    stops: Vec<Stop>,
+    pub interpolation: Interpolation,
+    pub mode: Mode,
+    /// Added to the value after scaling, shifting the gradient along.
+    pub offset: f64,
+    /// Multiplies the value before anything else.
+    pub scale: f64,
+    /// How many copies of the gradient fit between 0 and 1.
+    pub repeat: f64,
+}
+
+/// Names accepted by [`Palette::builtin`].
+pub const BUILTINS: [&str; 6] = ["fire", "gray", "ocean", "rainbow", "twilight", "ultra"];
+
+impl Palette {
+    /// A clamped RGB gradient through `stops`, which are sorted by position.
+    pub fn new(mut stops: Vec<Stop>) -> Self {
+        assert!(!stops.is_empty(), "A palette needs at least one stop.");
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        Self {
+            stops,
+            interpolation: Interpolation::Rgb,
+            mode: Mode::Clamped,
+            offset: 0.0,
+            scale: 1.0,
+            repeat: 1.0,
+        }
+    }
+
+    /// Evenly spaced stops.
+    pub fn even(colors: &[[u8; 3]]) -> Self {
+        let last = (colors.len() - 1).max(1) as f64;
+        Self::new(
+            colors
+                .iter()
+                .enumerate()
+                .map(|(i, &color)| Stop {
+                    position: i as f64 / last,
+                    color,
+                })
+                .collect(),
+        )
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        let palette = match name {
+            "fire" => Self::even(&[[0, 0, 0], [255, 0, 0], [255, 255, 0], [255, 255, 255]]),
+            "gray" => Self::even(&[[0, 0, 0], [255, 255, 255]]),
+            "ocean" => Self {
+                interpolation: Interpolation::Oklab,
+                ..Self::even(&[[0, 8, 32], [0, 64, 128], [32, 192, 224], [240, 255, 255]])
+            },
+            "rainbow" => Self {
+                interpolation: Interpolation::Hsv,
+                mode: Mode::Cyclic,
+                ..Self::even(&[[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 0, 0]])
+            },
+            "twilight" => Self {
+                interpolation: Interpolation::Oklab,
+                mode: Mode::Cyclic,
+                ..Self::even(&[
+                    [226, 217, 226],
+                    [94, 128, 185],
+                    [48, 19, 54],
+                    [170, 81, 72],
+                    [226, 217, 226],
+                ])
+            },
+            // The default gradient of Ultra Fractal.
+            "ultra" => Self {
+                interpolation: Interpolation::Oklab,
+                mode: Mode::Cyclic,
+                ..Self::new(vec![
+                    Stop {
+                        position: 0.0,
+                        color: [0, 7, 100],
+                    },
+                    Stop {
+                        position: 0.16,
+                        color: [32, 107, 203],
+                    },
+                    Stop {
+                        position: 0.42,
+                        color: [237, 255, 255],
+                    },
+                    Stop {
+                        position: 0.6425,
+                        color: [255, 170, 0],
+                    },
+                    Stop {
+                        position: 0.8575,
+                        color: [0, 2, 0],
+                    },
+                ])
+            },
+            _ => return None,
+        };
+        Some(palette)
+    }
+
+    /// Parses a built-in name, the path of a `.map`, `.ggr` or `.ugr`
+    /// gradient file (`file.ugr:name` picks an entry), or a comma-separated
+    /// list of `#rrggbb` stops with optional `@position`, e.g.
+    /// `#000000,#ff8000@0.3,#ffffff`. Stops without positions are spaced
+    /// evenly.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if let Some(palette) = Self::builtin(s.trim()) {
+            return Ok(palette);
+        }
+        if formats::is_gradient_file(s.trim()) {
+            return formats::load(s.trim());
+        }
+
+        let mut colors = Vec::new();
+        let mut positions = Vec::new();
+        for stop in s.split(',') {
+            let (color, position) = match stop.trim().split_once('@') {
+                Some((color, position)) => (color, Some(position)),
+                None => (stop.trim(), None),
+            };
+            colors.push(parse_hex(color)?);
+            if let Some(position) = position {
+                match position.trim().parse::<f64>() {
+                    Ok(p) if (0.0..=1.0).contains(&p) => positions.push(p),
+                    _ => return Err(format!("'{position}' is not a position between 0 and 1")),
+                }
+            }
+        }
+
+        if positions.is_empty() {
+            Ok(Self::even(&colors))
+        } else if positions.len() == colors.len() {
+            let stops = colors
+                .into_iter()
+                .zip(positions)
+                .map(|(color, position)| Stop { position, color })
+                .collect();
+            Ok(Self::new(stops))
+        } else {
+            Err("either all stops or none of them must have a position".to_string())
+        }
+    }
+
+    /// The color for `t`, usually the normalized iteration count in `[0, 1]`.
+    pub fn color(&self, t: f64) -> (u8, u8, u8) {
+        let u = t * self.scale + self.offset;
+        let g = match self.mode {
+            Mode::Cyclic => (u * self.repeat).rem_euclid(1.0),
+            Mode::Clamped => {
+                // Every copy covers (0, 1], so the top of the range still
+                // lands on the last stop.
+                let g = u.clamp(0.0, 1.0) * self.repeat;
+                g - (g.ceil() - 1.0).max(0.0)
+            }
+        };
+        if !g.is_finite() {
+            let [r, g, b] = self.stops[0].color;
+            return (r, g, b);
+        }
+
+        let [r, g, b] = self.sample(g);
+        (r, g, b)
+    }
+
+    /// The gradient at `g` in `[0, 1]`.
+    fn sample(&self, g: f64) -> [u8; 3] {
+        let first = self.stops[0];
+        let last = self.stops[self.stops.len() - 1];
+
+        if g < first.position || g >= last.position {
+            return match self.mode {
+                Mode::Clamped if g < first.position => first.color,
+                Mode::Clamped => last.color,
+                Mode::Cyclic => {
+                    let span = first.position + 1.0 - last.position;
+                    let f = (g - last.position).rem_euclid(1.0) / span;
+                    self.blend(last.color, first.color, f)
+                }
+            };
+        }
+
+        let i = self.stops.partition_point(|stop| stop.position <= g) - 1;
+        let (a, b) = (self.stops[i], self.stops[i + 1]);
+        self.blend(
+            a.color,
+            b.color,
+            (g - a.position) / (b.position - a.position),
+        )
+    }
+
+    fn blend(&self, a: [u8; 3], b: [u8; 3], f: f64) -> [u8; 3] {
+        let f = if f.is_finite() {
+            f.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let (a, b) = (to_unit(a), to_unit(b));
+        let mixed = match self.interpolation {
+            Interpolation::Rgb => lerp(a, b, f),
+            Interpolation::Hsv => {
+                let (a, mut b) = (rgb_to_hsv(a), rgb_to_hsv(b));
+                if b[0] - a[0] > 0.5 {
+                    b[0] -= 1.0;
+                } else if a[0] - b[0] > 0.5 {
+                    b[0] += 1.0;
+                }
+                let mut hsv = lerp(a, b, f);
+                hsv[0] = hsv[0].rem_euclid(1.0);
+                hsv_to_rgb(hsv)
+            }
+            Interpolation::Oklab => oklab_to_rgb(lerp(rgb_to_oklab(a), rgb_to_oklab(b), f)),
+        };
+        mixed.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+fn parse_hex(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.trim().trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+    };
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok([r, g, b]),
+        _ => Err(format!(
+            "'{}' is neither a palette name nor a `#rrggbb` color",
+            s.trim()
+        )),
+    }
+}
+
+fn to_unit(color: [u8; 3]) -> [f64; 3] {
+    color.map(|v| v as f64 / 255.0)
+}
+
+fn lerp(a: [f64; 3], b: [f64; 3], f: f64) -> [f64; 3] {
+    [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * f)
+}
+
+fn rgb_to_hsv([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    [hue / 6.0, saturation, max]
+}
+
+fn hsv_to_rgb([h, s, v]: [f64; 3]) -> [f64; 3] {
+    let h = h * 6.0;
+    let c = v * s;
+    let x = c * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let m = v - c;
+    let [r, g, b] = match h as usize {
+        0 => [c, x, 0.0],
+        1 => [x, c, 0.0],
+        2 => [0.0, c, x],
+        3 => [0.0, x, c],
+        4 => [x, 0.0, c],
+        _ => [c, 0.0, x],
+    };
+    [r + m, g + m, b + m]
+}
+
+fn srgb_to_linear(v: f64) -> f64 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f64) -> f64 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// https://bottosson.github.io/posts/oklab/
+fn rgb_to_oklab(rgb: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+fn oklab_to_rgb([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    [
+        4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_,
+        -1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_,
+        -0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076147010 * s_,
+    ]
+    .map(linear_to_srgb)
+}
+
+#[test]
+fn test_palette() {
+    let fire = Palette::builtin("fire").unwrap();
+    assert!(fire.color(0.0) == (0, 0, 0));
+    assert!(fire.color(1.0 / 3.0) == (255, 0, 0));
+    assert!(fire.color(0.5) == (255, 128, 0));
+    assert!(fire.color(2.0) == (255, 255, 255), "Clamping failed.");
+
+    let mut gray = Palette::parse("#000000,#ffffff").unwrap();
+    gray.repeat = 2.0;
+    assert!(gray.color(0.25) == (128, 128, 128));
+    assert!(
+        gray.color(1.0) == (255, 255, 255),
+        "The last stop is unreachable."
+    );
+    gray.repeat = 1.0;
+    gray.offset = 0.5;
+    assert!(
+        gray.color(0.0) == (128, 128, 128),
+        "The offset was ignored."
+    );
+
+    let mut cyclic = Palette::parse("#000000@0.25,#ffffff@0.75").unwrap();
+    cyclic.mode = Mode::Cyclic;
+    assert!(
+        cyclic.color(0.0) == (128, 128, 128),
+        "Cyclic wrap-around failed."
+    );
+    assert!(cyclic.color(1.75) == (255, 255, 255));
+
+    assert!(Palette::parse("#000000@0,#ffffff").is_err());
+    assert!(Palette::parse("nope").is_err());
+    for name in BUILTINS {
+        assert!(Palette::builtin(name).is_some(), "Missing built-in {name}.");
+    }
+}
+
+#[test]
+fn test_color_spaces() {
+    let colors = [[0, 0, 0], [255, 255, 255], [12, 200, 97], [255, 0, 128]];
+    for color in colors {
+        let unit = to_unit(color);
+        let round = |c: [f64; 3]| c.map(|v| (v * 255.0).round() as u8);
+        assert!(round(hsv_to_rgb(rgb_to_hsv(unit))) == color);
+        assert!(round(oklab_to_rgb(rgb_to_oklab(unit))) == color);
+    }
+
+    let mut palette = Palette::parse("#ff0000,#0000ff").unwrap();
+    palette.interpolation = Interpolation::Hsv;
+    assert!(
+        palette.color(0.5) == (255, 0, 255),
+        "Hue took the long way."
+    );
+}
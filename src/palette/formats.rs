@@ -0,0 +1,440 @@
+//! Gradient files from other fractal programs: Fractint color maps (`.map`),
+//! GIMP gradients (`.ggr`) and Ultra Fractal gradient collections (`.ugr`).
+
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use super::{hsv_to_rgb, lerp, rgb_to_hsv, Mode, Palette, Stop};
+
+/// Number of positions in an Ultra Fractal gradient.
+const UGR_POSITIONS: f64 = 400.0;
+
+/// Samples taken from GIMP segments that are not a plain RGB blend.
+const GGR_SAMPLES: usize = 16;
+
+/// Whether `path` looks like a gradient file, optionally followed by
+/// `:name` to pick an entry from a `.ugr` collection.
+pub fn is_gradient_file(path: &str) -> bool {
+    let (path, _) = split_entry(path);
+    matches!(extension(path).as_deref(), Some("map" | "ggr" | "ugr"))
+}
+
+/// Loads a gradient file, choosing the format by its extension.
+pub fn load(path: &str) -> Result<Palette, String> {
+    let (path, entry) = split_entry(path);
+    let text = fs::read_to_string(path).map_err(|err| format!("could not read '{path}': {err}"))?;
+    let palette = match extension(path).as_deref() {
+        Some("map") => parse_map(&text),
+        Some("ggr") => parse_ggr(&text),
+        Some("ugr") => parse_ugr(&text, entry),
+        _ => Err("unknown gradient format".to_string()),
+    };
+    palette.map_err(|err| format!("{path}: {err}"))
+}
+
+fn split_entry(path: &str) -> (&str, Option<&str>) {
+    match path.rsplit_once(':') {
+        Some((file, entry)) if file.to_ascii_lowercase().ends_with(".ugr") => (file, Some(entry)),
+        _ => (path, None),
+    }
+}
+
+fn extension(path: &str) -> Option<String> {
+    let extension = Path::new(path).extension()?.to_str()?;
+    Some(extension.to_ascii_lowercase())
+}
+
+/// A Fractint color map: one `red green blue` line per color index, each
+/// value from 0 to 255, optionally followed by a comment. The colors are
+/// spaced evenly and wrap around, as color cycling does in Fractint.
+pub fn parse_map(text: &str) -> Result<Palette, String> {
+    let mut colors = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        let values = line
+            .split_whitespace()
+            .take(3)
+            .map(|v| v.parse::<u8>())
+            .collect::<Result<Vec<_>, _>>();
+        match values.as_deref() {
+            Ok(&[r, g, b]) => colors.push([r, g, b]),
+            _ => {
+                return Err(format!(
+                    "line {}: expected three color values from 0 to 255",
+                    n + 1
+                ))
+            }
+        }
+    }
+    if colors.is_empty() {
+        return Err("the color map is empty".to_string());
+    }
+
+    let count = colors.len() as f64;
+    let stops = colors
+        .into_iter()
+        .enumerate()
+        .map(|(i, color)| Stop {
+            position: i as f64 / count,
+            color,
+        })
+        .collect();
+    Ok(Palette {
+        mode: Mode::Cyclic,
+        ..Palette::new(stops)
+    })
+}
+
+/// A GIMP gradient: a header, an optional name, the number of segments and
+/// one line per segment with its left, middle and right positions, the
+/// RGBA colors at both ends, the blending function and the coloring type.
+pub fn parse_ggr(text: &str) -> Result<Palette, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(n, line)| (n + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    match lines.next() {
+        Some((_, "GIMP Gradient")) => {}
+        _ => return Err("missing the 'GIMP Gradient' header".to_string()),
+    }
+    let (n, mut line) = lines.next().ok_or("missing the segment count")?;
+    if line.starts_with("Name:") {
+        (_, line) = lines.next().ok_or("missing the segment count")?;
+    }
+    let count: usize = line
+        .parse()
+        .map_err(|_| format!("line {n}: '{line}' is not a segment count"))?;
+
+    let mut stops: Vec<Stop> = Vec::new();
+    let mut push = |stop: Stop| {
+        if stops.last() != Some(&stop) {
+            stops.push(stop);
+        }
+    };
+    let mut segments = 0;
+    for (n, line) in lines {
+        let segment = GgrSegment::parse(line).map_err(|err| format!("line {n}: {err}"))?;
+        segments += 1;
+
+        if segment.is_plain() {
+            push(Stop {
+                position: segment.left,
+                color: to_u8(segment.left_color),
+            });
+            push(Stop {
+                position: segment.right,
+                color: to_u8(segment.right_color),
+            });
+        } else {
+            for i in 0..=GGR_SAMPLES {
+                let position = lerp_f64(segment.left, segment.right, i as f64 / GGR_SAMPLES as f64);
+                push(Stop {
+                    position,
+                    color: to_u8(segment.color(position)),
+                });
+            }
+        }
+    }
+
+    if segments != count {
+        return Err(format!(
+            "the header announces {count} segments but there are {segments}"
+        ));
+    }
+    if stops.is_empty() {
+        return Err("the gradient has no segments".to_string());
+    }
+    Ok(Palette::new(stops))
+}
+
+struct GgrSegment {
+    left: f64,
+    middle: f64,
+    right: f64,
+    left_color: [f64; 3],
+    right_color: [f64; 3],
+    blending: u8,
+    coloring: u8,
+}
+
+impl GgrSegment {
+    fn parse(line: &str) -> Result<Self, String> {
+        let values = line
+            .split_whitespace()
+            .map(|v| v.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "a segment may only contain numbers".to_string())?;
+        if values.len() < 13 {
+            return Err(format!("a segment needs 13 values, found {}", values.len()));
+        }
+
+        let segment = Self {
+            left: values[0],
+            middle: values[1],
+            right: values[2],
+            left_color: [values[3], values[4], values[5]],
+            right_color: [values[7], values[8], values[9]],
+            blending: values[11] as u8,
+            coloring: values[12] as u8,
+        };
+        if !(0.0 <= segment.left
+            && segment.left <= segment.middle
+            && segment.middle <= segment.right
+            && segment.right <= 1.0)
+        {
+            return Err("the segment positions must be ordered within 0 to 1".to_string());
+        }
+        if values[11] != segment.blending as f64 || segment.blending > 5 {
+            return Err(format!("unknown blending function {}", values[11]));
+        }
+        if values[12] != segment.coloring as f64 || segment.coloring > 2 {
+            return Err(format!("unknown coloring type {}", values[12]));
+        }
+        Ok(segment)
+    }
+
+    /// A linear RGB blend with the middle halfway, which two stops describe
+    /// exactly.
+    fn is_plain(&self) -> bool {
+        let center = (self.left + self.right) / 2.0;
+        self.blending == 0 && self.coloring == 0 && (self.middle - center).abs() < 1e-6
+    }
+
+    /// The color at `position`, following GIMP's blending functions.
+    fn color(&self, position: f64) -> [f64; 3] {
+        let length = self.right - self.left;
+        let (p, middle) = if length > 0.0 {
+            (
+                (position - self.left) / length,
+                (self.middle - self.left) / length,
+            )
+        } else {
+            (0.5, 0.5)
+        };
+        let linear = if p <= middle {
+            if middle > 0.0 {
+                0.5 * p / middle
+            } else {
+                0.0
+            }
+        } else if middle < 1.0 {
+            0.5 + 0.5 * (p - middle) / (1.0 - middle)
+        } else {
+            1.0
+        };
+        let f = match self.blending {
+            0 => linear,
+            1 => p.powf(0.5f64.ln() / middle.max(1e-10).ln()),
+            2 => ((-PI / 2.0 + PI * linear).sin() + 1.0) / 2.0,
+            3 => (1.0 - (linear - 1.0).powi(2)).sqrt(),
+            4 => 1.0 - (1.0 - linear.powi(2)).sqrt(),
+            _ => {
+                if p < middle {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        };
+
+        match self.coloring {
+            0 => lerp(self.left_color, self.right_color, f),
+            coloring => {
+                let a = rgb_to_hsv(self.left_color);
+                let mut b = rgb_to_hsv(self.right_color);
+                // 1 turns counter-clockwise (increasing hue), 2 clockwise.
+                if coloring == 1 && b[0] < a[0] {
+                    b[0] += 1.0;
+                } else if coloring == 2 && b[0] > a[0] {
+                    b[0] -= 1.0;
+                }
+                let mut hsv = lerp(a, b, f);
+                hsv[0] = hsv[0].rem_euclid(1.0);
+                hsv_to_rgb(hsv)
+            }
+        }
+    }
+}
+
+/// An Ultra Fractal gradient collection: entries of the form
+/// `name { gradient: ... index=N color=C ... }`, where colors are packed as
+/// `red + 256 * green + 65536 * blue` at indices from 0 to 399. Picks the
+/// entry named `entry`, or the first one.
+pub fn parse_ugr(text: &str, entry: Option<&str>) -> Result<Palette, String> {
+    let mut current: Option<&str> = None;
+    let mut in_gradient = false;
+    let mut found = false;
+    let mut index: Option<f64> = None;
+    let mut stops = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if let Some(name) = line.strip_suffix('{') {
+            current = Some(name.trim());
+            continue;
+        }
+        if line == "}" {
+            if found {
+                break;
+            }
+            current = None;
+            in_gradient = false;
+            continue;
+        }
+        let Some(name) = current else {
+            continue;
+        };
+        if line.ends_with(':') {
+            in_gradient = line == "gradient:";
+            if in_gradient && entry.is_none_or(|entry| entry == name) {
+                found = true;
+            }
+            continue;
+        }
+        if !(in_gradient && found) {
+            continue;
+        }
+
+        for pair in line.split_whitespace() {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let number = || {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| format!("line {}: '{value}' is not a valid {key}", n + 1))
+            };
+            match key {
+                "index" => {
+                    let i = number()?;
+                    if i as f64 >= UGR_POSITIONS {
+                        return Err(format!("line {}: index {i} is out of range", n + 1));
+                    }
+                    index = Some(i as f64);
+                }
+                "color" => {
+                    let Some(position) = index.take() else {
+                        return Err(format!("line {}: a color without an index", n + 1));
+                    };
+                    let c = number()?;
+                    stops.push(Stop {
+                        position: position / UGR_POSITIONS,
+                        color: [c as u8, (c >> 8) as u8, (c >> 16) as u8],
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if !found {
+        return Err(match entry {
+            Some(entry) => format!("there is no gradient named '{entry}'"),
+            None => "the file contains no gradient".to_string(),
+        });
+    }
+    if stops.is_empty() {
+        return Err("the gradient has no colors".to_string());
+    }
+    Ok(Palette {
+        mode: Mode::Cyclic,
+        ..Palette::new(stops)
+    })
+}
+
+fn lerp_f64(a: f64, b: f64, f: f64) -> f64 {
+    a + (b - a) * f
+}
+
+fn to_u8(color: [f64; 3]) -> [u8; 3] {
+    color.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+#[test]
+fn test_map() {
+    let text = include_str!("../../palettes/volcano.map");
+    let palette = parse_map(text).unwrap();
+    let entries = text.lines().collect::<Vec<_>>();
+
+    assert!(palette.mode == Mode::Cyclic);
+    assert!(palette.stops.len() == entries.len());
+    for (i, line) in entries.iter().enumerate() {
+        let (r, g, b) = palette.color(i as f64 / entries.len() as f64);
+        assert!(
+            format!("{r} {g} {b}")
+                == line
+                    .split_whitespace()
+                    .take(3)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            "Color {i} does not match the map file."
+        );
+    }
+
+    assert!(parse_map("").is_err(), "An empty map was accepted.");
+    assert!(
+        parse_map("0 0 0\n1 2 300\n")
+            == Err("line 2: expected three color values from 0 to 255".to_string())
+    );
+}
+
+#[test]
+fn test_ggr() {
+    let text = include_str!("../../palettes/sunrise.ggr");
+    let palette = parse_ggr(text).unwrap();
+
+    for line in text.lines().skip(3) {
+        let values = line
+            .split_whitespace()
+            .map(|v| v.parse::<f64>().unwrap())
+            .collect::<Vec<_>>();
+        let left = to_u8([values[3], values[4], values[5]]);
+        let right = to_u8([values[7], values[8], values[9]]);
+        let (r, g, b) = palette.color(values[0]);
+        assert!(
+            [r, g, b] == left,
+            "The segment at {} starts wrong.",
+            values[0]
+        );
+        // A hard edge at the right end belongs to the next segment.
+        let (r, g, b) = palette.color(values[2] - 1e-9);
+        let close = |a: u8, b: u8| a.abs_diff(b) <= 1;
+        assert!(
+            close(r, right[0]) && close(g, right[1]) && close(b, right[2]),
+            "The segment at {} ends wrong.",
+            values[0]
+        );
+    }
+
+    assert!(parse_ggr("GIMP Palette\n").is_err());
+    assert!(parse_ggr("GIMP Gradient\n2\n0 0.5 1 0 0 0 1 1 1 1 1 0 0\n").is_err());
+    assert!(parse_ggr("GIMP Gradient\n1\n0 0.5 1 0 0 0 1 1 1 1 1 9 0\n")
+        .unwrap_err()
+        .contains("line 3"));
+}
+
+#[test]
+fn test_ugr() {
+    let text = include_str!("../../palettes/classic.ugr");
+    let first = parse_ugr(text, None).unwrap();
+    let dusk = parse_ugr(text, Some("Dusk")).unwrap();
+
+    assert!(first.mode == Mode::Cyclic);
+    assert!(first.stops[0].color == [0, 7, 100]);
+    assert!(first.color(0.0) == (0, 7, 100));
+    assert!(first.color(64.0 / UGR_POSITIONS) == (32, 107, 203));
+    assert!(dusk.color(200.0 / UGR_POSITIONS) == (255, 128, 0));
+    assert!(first != dusk, "The entry name was ignored.");
+
+    assert!(parse_ugr(text, Some("Missing")).is_err());
+    assert!(parse_ugr("a {\ngradient:\ncolor=5\n}\n", None)
+        .unwrap_err()
+        .contains("without an index"));
+}
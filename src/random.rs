@@ -0,0 +1,61 @@
+/// A small, fast pseudo-random generator (SplitMix64). The same seed always
+/// gives the same sequence, which keeps sampled renders reproducible.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// An independent generator for one of many parallel streams, such as a
+    /// chunk of samples.
+    pub fn stream(seed: u64, index: u64) -> Self {
+        let mut rng = Self::new(seed ^ index.wrapping_mul(0xd1b5_4a32_d192_ed03));
+        rng.next_u64();
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number in `[from, to)`.
+    pub fn range(&mut self, from: f64, to: f64) -> f64 {
+        from + (to - from) * self.next_f64()
+    }
+}
+
+#[test]
+fn test_rng() {
+    let take = |mut rng: Rng| (0..100).map(|_| rng.next_u64()).collect::<Vec<_>>();
+    assert!(
+        take(Rng::new(7)) == take(Rng::new(7)),
+        "The same seed gave different numbers."
+    );
+    assert!(take(Rng::new(7)) != take(Rng::new(8)));
+    assert!(take(Rng::stream(7, 0)) != take(Rng::stream(7, 1)));
+
+    let mut rng = Rng::new(1);
+    let mut sum = 0.0;
+    for _ in 0..10000 {
+        let x = rng.range(-1.0, 3.0);
+        assert!((-1.0..3.0).contains(&x));
+        sum += x;
+    }
+    assert!(
+        (sum / 10000.0 - 1.0).abs() < 0.05,
+        "The numbers are not uniform."
+    );
+}
@@ -0,0 +1,183 @@
+use crate::average::{self, Accumulator, Stripes, TriangleInequality};
+use crate::fractal::{self, Biomorph, Escape, Fractal};
+use crate::palette::Palette;
+use crate::trap::{self, Trap};
+use crate::Complex;
+
+/// Computes the color of a point of the complex plane. Shaders are shared
+/// between the rendering threads.
+pub trait Shader: Sync {
+    fn shade(&self, point: Complex) -> (u8, u8, u8);
+}
+
+/// What an escape-time pixel is colored by.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Coloring {
+    /// The number of iterations.
+    Iterations,
+    /// Pickover's biomorphs: the orbit stops once `|re z|` or `|im z|`
+    /// passes the bailout, and the color tells which components stayed
+    /// within it: none, the real part, the imaginary part or both.
+    Biomorph,
+    /// Pickover's stalks: orbits that come within `width` of the axes are
+    /// colored by how close they came, the others by their iterations.
+    Stalks { width: f64 },
+    /// How close orbits come to a trap. Distances `d` are colored by
+    /// `1 / (1 + d / size)`, which is 1 on the trap itself; in hit mode,
+    /// orbits are colored by the iteration they fell in at, or by the image
+    /// of an image trap, and the others by their iterations.
+    Trap(Trap),
+    /// The triangle inequality average of the orbit.
+    TriangleInequality,
+    /// The stripe average of the orbit, with `density` stripes per turn.
+    Stripes { density: f64 },
+}
+
+/// Colors a [`Fractal`] by how many iterations its orbits take to escape.
+pub struct EscapeTime {
+    pub fractal: Box<dyn Fractal>,
+    pub palette: Palette,
+    pub max_iterations: usize,
+    pub smooth: bool,
+    pub bailout: f64,
+    pub coloring: Coloring,
+}
+
+impl EscapeTime {
+    /// The iteration count as a value in `[0, 1]`.
+    fn iterations(&self, escape: &Escape) -> f64 {
+        let value = if self.smooth {
+            escape.smooth(self.max_iterations, self.fractal.degree())
+        } else {
+            escape.iterations as f64
+        };
+        value / self.max_iterations as f64
+    }
+
+    /// The value `accumulator` gathers over the orbit of `point`,
+    /// interpolated by how far the orbit got past the bailout.
+    fn average(&self, point: Complex, accumulator: &mut dyn Accumulator) -> f64 {
+        let bailout_sqr = self.bailout * self.bailout;
+        let escape = average::accumulate(
+            self.fractal.as_ref(),
+            point,
+            bailout_sqr,
+            self.max_iterations,
+            accumulator,
+        );
+        accumulator.value(escape.fraction(self.max_iterations, self.bailout, self.fractal.degree()))
+    }
+}
+
+impl Shader for EscapeTime {
+    fn shade(&self, point: Complex) -> (u8, u8, u8) {
+        let fractal = self.fractal.as_ref();
+        let bailout_sqr = self.bailout * self.bailout;
+        let value = match &self.coloring {
+            Coloring::Iterations => {
+                let escape = fractal::iterate(fractal, point, self.bailout, self.max_iterations);
+                self.iterations(&escape)
+            }
+            Coloring::Biomorph => {
+                let escape = Biomorph(fractal).escape(point, bailout_sqr, self.max_iterations);
+                let real = escape.z.re * escape.z.re <= bailout_sqr;
+                let imaginary = escape.z.im * escape.z.im <= bailout_sqr;
+                (real as u8 + 2 * imaginary as u8) as f64 / 3.0
+            }
+            Coloring::Stalks { width } => {
+                let mut nearest = f64::INFINITY;
+                let escape =
+                    fractal.escape_with(point, bailout_sqr, self.max_iterations, &mut |orbit| {
+                        nearest = nearest.min(orbit.z.re.abs()).min(orbit.z.im.abs())
+                    });
+                if nearest < *width {
+                    1.0 - nearest / width
+                } else {
+                    self.iterations(&escape)
+                }
+            }
+            Coloring::Trap(trap) => {
+                let (escape, trapped) =
+                    trap.follow(fractal, point, bailout_sqr, self.max_iterations);
+                let closeness = |distance: f64| 1.0 / (1.0 + distance / trap.size);
+                match (trap.mode, trapped.hit) {
+                    (trap::Mode::Minimum, _) => closeness(trapped.nearest),
+                    (trap::Mode::Average, _) => closeness(trapped.average),
+                    (trap::Mode::Hit, Some(hit)) => {
+                        if let Some(color) = trap.pixel(hit.z) {
+                            return color;
+                        }
+                        hit.iteration as f64 / self.max_iterations as f64
+                    }
+                    (trap::Mode::Hit, None) => self.iterations(&escape),
+                }
+            }
+            Coloring::TriangleInequality => {
+                self.average(point, &mut TriangleInequality::new(fractal.degree()))
+            }
+            Coloring::Stripes { density } => self.average(point, &mut Stripes::new(*density)),
+        };
+        self.palette.color(value)
+    }
+}
+
+#[test]
+fn test_colorings() {
+    let shader = |coloring| EscapeTime {
+        fractal: Box::new(fractal::Julia {
+            c: Complex::new(-0.1, 0.1),
+        }),
+        palette: Palette::builtin("gray").unwrap(),
+        max_iterations: 50,
+        smooth: false,
+        bailout: 10.0,
+        coloring,
+    };
+
+    let biomorph = shader(Coloring::Biomorph);
+    let gray = |value: f64| {
+        let v = (value * 255.0).round() as u8;
+        (v, v, v)
+    };
+    assert!(
+        biomorph.shade(Complex::ZERO) == gray(1.0),
+        "The interior is not white."
+    );
+    // Both parts start out of bounds, or only one of them.
+    assert!(biomorph.shade(Complex::new(11.0, 11.0)) == gray(0.0));
+    assert!(biomorph.shade(Complex::new(11.0, 0.5)) == gray(2.0 / 3.0));
+    assert!(biomorph.shade(Complex::new(0.5, 11.0)) == gray(1.0 / 3.0));
+
+    // A point whose orbit passes close to the real axis before it escapes.
+    let stalks = shader(Coloring::Stalks { width: 0.1 });
+    let plain = shader(Coloring::Iterations);
+    let point = Complex::new(3.0, -0.01);
+    let z = point * point + Complex::new(-0.1, 0.1);
+    assert!(z.im.abs() < 0.1 && z.re.abs() > 1.0);
+    assert!(stalks.shade(point) == plain.palette.color(1.0 - z.im.abs() / 0.1));
+    let far = Complex::new(2.5, 2.5);
+    assert!(stalks.shade(far) == plain.shade(far));
+
+    // The same orbit is the nearest to a point trap at its first step.
+    let trap = |mode| Trap {
+        shape: trap::Shape::Point,
+        center: z,
+        size: 0.5,
+        mode,
+    };
+    let minimum = shader(Coloring::Trap(trap(trap::Mode::Minimum)));
+    assert!(minimum.shade(point) == plain.palette.color(1.0));
+    let hit = shader(Coloring::Trap(trap(trap::Mode::Hit)));
+    assert!(hit.shade(point) == plain.palette.color(1.0 / 50.0));
+    assert!(
+        hit.shade(far) == plain.shade(far),
+        "A missed trap changed the color."
+    );
+
+    // An orbit escaping in one step is averaged over that step alone.
+    let stripes = shader(Coloring::Stripes { density: 3.0 });
+    let escaping = Complex::new(0.0, 5.0);
+    let z = escaping * escaping + Complex::new(-0.1, 0.1);
+    let expected = 0.5 + 0.5 * (3.0 * z.arg()).sin();
+    assert!(stripes.shade(escaping) == plain.palette.color(expected));
+}
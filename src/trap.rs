@@ -0,0 +1,281 @@
+use std::fs::File;
+use std::io::Read;
+
+use clap::ValueEnum;
+
+use crate::fractal::{Escape, Fractal};
+use crate::Complex;
+
+/// The shape orbits are caught by, placed around the trap's center.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Point,
+    /// The line through the center at `angle` radians.
+    Line {
+        angle: f64,
+    },
+    /// Two perpendicular lines through the center, the first at `angle`.
+    Cross {
+        angle: f64,
+    },
+    Circle {
+        radius: f64,
+    },
+    /// A picture centered on the trap, `2 * size` wide. Distances are
+    /// measured to its rectangle, and only its opaque pixels catch orbits.
+    Image(Picture),
+}
+
+/// What a trapped orbit is colored by.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Mode {
+    /// The closest the orbit came to the trap.
+    Minimum,
+    /// The mean distance of the orbit from the trap.
+    Average,
+    /// The iteration at which the orbit first fell into the trap; orbits
+    /// that never do are colored by their iterations.
+    Hit,
+}
+
+/// An orbit trap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trap {
+    pub shape: Shape,
+    pub center: Complex,
+    /// How close an orbit must come to fall into the trap, and the scale
+    /// distances are measured in.
+    pub size: f64,
+    pub mode: Mode,
+}
+
+/// Where an orbit first fell into a trap.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    pub iteration: usize,
+    pub z: Complex,
+}
+
+/// What a trap recorded about an orbit.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Trapped {
+    pub nearest: f64,
+    pub average: f64,
+    pub hit: Option<Hit>,
+}
+
+impl Trap {
+    /// The distance from `z` to the trap.
+    pub fn distance(&self, z: Complex) -> f64 {
+        let offset = z - self.center;
+        match &self.shape {
+            Shape::Point => offset.abs(),
+            Shape::Line { angle } => (offset * Complex::from_polar(1.0, -angle)).im.abs(),
+            Shape::Cross { angle } => {
+                let w = offset * Complex::from_polar(1.0, -angle);
+                w.re.abs().min(w.im.abs())
+            }
+            Shape::Circle { radius } => (offset.abs() - radius).abs(),
+            Shape::Image(picture) => {
+                let (half_width, half_height) = picture.half_extent(self.size);
+                let dx = (offset.re.abs() - half_width).max(0.0);
+                let dy = (offset.im.abs() - half_height).max(0.0);
+                dx.hypot(dy)
+            }
+        }
+    }
+
+    /// Whether an orbit at `z` falls into the trap.
+    fn catches(&self, z: Complex) -> bool {
+        match &self.shape {
+            Shape::Image(_) => self.pixel(z).is_some(),
+            _ => self.distance(z) < self.size,
+        }
+    }
+
+    /// The color of the opaque pixel of an image trap under `z`, if there is
+    /// one.
+    pub fn pixel(&self, z: Complex) -> Option<(u8, u8, u8)> {
+        let Shape::Image(picture) = &self.shape else {
+            return None;
+        };
+        let (half_width, half_height) = picture.half_extent(self.size);
+        let offset = z - self.center;
+        let u = (offset.re / half_width + 1.0) / 2.0;
+        let v = (offset.im / half_height + 1.0) / 2.0;
+        picture.opaque(u, v)
+    }
+
+    /// Iterates the orbit of `pixel` like [`Fractal::escape`], recording its
+    /// distances from the trap along the way.
+    pub fn follow(
+        &self,
+        fractal: &dyn Fractal,
+        pixel: Complex,
+        bailout_sqr: f64,
+        max_iterations: usize,
+    ) -> (Escape, Trapped) {
+        let mut nearest = f64::INFINITY;
+        let mut total = 0.0;
+        let mut steps = 0;
+        let mut hit = None;
+        let escape = fractal.escape_with(pixel, bailout_sqr, max_iterations, &mut |orbit| {
+            let distance = self.distance(orbit.z);
+            nearest = nearest.min(distance);
+            total += distance;
+            steps += 1;
+            if hit.is_none() && self.catches(orbit.z) {
+                hit = Some(Hit {
+                    iteration: steps,
+                    z: orbit.z,
+                });
+            }
+        });
+        let average = if steps > 0 {
+            total / steps as f64
+        } else {
+            f64::INFINITY
+        };
+        (
+            escape,
+            Trapped {
+                nearest,
+                average,
+                hit,
+            },
+        )
+    }
+}
+
+/// An RGBA image for image traps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Picture {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Picture {
+    /// Reads a PNG file.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|err| format!("could not read '{path}': {err}"))?;
+        Self::decode(file).map_err(|err| format!("{path}: {err}"))
+    }
+
+    pub fn decode(png: impl Read) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(png);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut data)
+            .map_err(|err| err.to_string())?;
+        let data = &data[..info.buffer_size()];
+
+        let pixels = match info.color_type {
+            png::ColorType::Grayscale => data.iter().map(|&l| [l, l, l, 255]).collect(),
+            png::ColorType::GrayscaleAlpha => {
+                data.chunks(2).map(|p| [p[0], p[0], p[0], p[1]]).collect()
+            }
+            png::ColorType::Rgb => data.chunks(3).map(|p| [p[0], p[1], p[2], 255]).collect(),
+            png::ColorType::Rgba => data.chunks(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+            png::ColorType::Indexed => return Err("unexpanded palette image".to_string()),
+        };
+        Ok(Self {
+            width: info.width as usize,
+            height: info.height as usize,
+            pixels,
+        })
+    }
+
+    /// Half the width and height of the picture when it is `2 * size` wide.
+    fn half_extent(&self, size: f64) -> (f64, f64) {
+        (size, size * self.height as f64 / self.width as f64)
+    }
+
+    /// The color at `(u, v)`, both between 0 and 1 with `v` growing down the
+    /// picture, unless it falls outside or on a transparent pixel.
+    fn opaque(&self, u: f64, v: f64) -> Option<(u8, u8, u8)> {
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return None;
+        }
+        let x = (u * self.width as f64) as usize;
+        let y = (v * self.height as f64) as usize;
+        let [r, g, b, a] = self.pixels[y * self.width + x];
+        (a >= 128).then_some((r, g, b))
+    }
+}
+
+#[test]
+fn test_distances() {
+    let trap = |shape| Trap {
+        shape,
+        center: Complex::new(1.0, 1.0),
+        size: 0.1,
+        mode: Mode::Minimum,
+    };
+    let z = Complex::new(4.0, 5.0);
+    let near = |a: f64, b: f64| (a - b).abs() < 1e-12;
+    assert!(near(trap(Shape::Point).distance(z), 5.0));
+    assert!(near(trap(Shape::Line { angle: 0.0 }).distance(z), 4.0));
+    assert!(near(
+        trap(Shape::Line {
+            angle: std::f64::consts::FRAC_PI_2
+        })
+        .distance(z),
+        3.0
+    ));
+    assert!(near(trap(Shape::Cross { angle: 0.0 }).distance(z), 3.0));
+    assert!(near(trap(Shape::Circle { radius: 2.0 }).distance(z), 3.0));
+    assert!(near(trap(Shape::Circle { radius: 7.0 }).distance(z), 2.0));
+}
+
+#[test]
+fn test_follow() {
+    use crate::fractal::Julia;
+
+    // z -> z^2 with z starting at 0.5 goes 0.25, 0.0625, ... towards 0.
+    let fractal = Julia { c: Complex::ZERO };
+    let trap = Trap {
+        shape: Shape::Circle { radius: 0.1 },
+        center: Complex::ZERO,
+        size: 0.05,
+        mode: Mode::Hit,
+    };
+    let (escape, trapped) = trap.follow(&fractal, Complex::new(0.5, 0.0), 4.0, 3);
+    assert!(escape.iterations == 3);
+    let hit = trapped.hit.expect("The orbit passed through the trap.");
+    assert!(hit.iteration == 2 && hit.z == Complex::new(0.0625, 0.0));
+    assert!((trapped.nearest - 0.0375).abs() < 1e-12);
+    let average = (0.15 + 0.0375 + (0.1 - 0.00390625)) / 3.0;
+    assert!((trapped.average - average).abs() < 1e-12);
+}
+
+#[test]
+fn test_image_trap() {
+    // A 2x1 picture: an opaque red pixel next to a transparent one.
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, 2, 1);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer
+        .write_image_data(&[255, 0, 0, 255, 0, 0, 255, 0])
+        .unwrap();
+    writer.finish().unwrap();
+
+    let trap = Trap {
+        shape: Shape::Image(Picture::decode(png.as_slice()).unwrap()),
+        center: Complex::ZERO,
+        size: 1.0,
+        mode: Mode::Hit,
+    };
+    assert!(trap.pixel(Complex::new(-0.5, 0.2)) == Some((255, 0, 0)));
+    assert!(
+        trap.pixel(Complex::new(0.5, 0.2)).is_none(),
+        "A transparent pixel caught the orbit."
+    );
+    assert!(trap.pixel(Complex::new(-0.5, 0.6)).is_none());
+    assert!(trap.distance(Complex::new(0.5, 0.2)) == 0.0);
+    assert!((trap.distance(Complex::new(4.0, 4.5)) - 5.0).abs() < 1e-12);
+}